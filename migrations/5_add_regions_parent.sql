@@ -0,0 +1,2 @@
+ALTER TABLE regions
+    ADD COLUMN IF NOT EXISTS parent_id INT;
\ No newline at end of file
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS reindeer
+(
+    id         SERIAL PRIMARY KEY,
+    name       TEXT  NOT NULL UNIQUE,
+    attributes JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS contests
+(
+    id      SERIAL PRIMARY KEY,
+    held_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE TABLE IF NOT EXISTS contest_entries
+(
+    contest_id INT REFERENCES contests (id) ON DELETE CASCADE,
+    name       TEXT  NOT NULL,
+    attributes JSONB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS contest_placings
+(
+    contest_id INT REFERENCES contests (id) ON DELETE CASCADE,
+    name       TEXT NOT NULL,
+    category   TEXT NOT NULL,
+    rank       INT  NOT NULL,
+    score      DOUBLE PRECISION
+);
\ No newline at end of file
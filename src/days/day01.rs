@@ -1,26 +1,166 @@
-use axum::extract::Path;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::ops::BitXor;
+use std::str::FromStr;
+
+use axum::extract::{Path, Query};
 use axum::http::StatusCode;
-use axum::routing::get;
-use axum::Router;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+
+mod eval;
 
 pub fn get_day_1_router() -> Router {
-    Router::new().route("/*l_nums", get(cube_the_bits))
+    Router::new()
+        .route("/eval", post(evaluate))
+        .route("/*l_nums", get(cube_the_bits))
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum NumberType {
+    #[default]
+    I64,
+    U128,
+    BigInt,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Overflow {
+    #[default]
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Deserialize, Debug)]
+struct Calculator {
+    #[serde(default, rename = "type")]
+    number_type: NumberType,
+    #[serde(default)]
+    overflow: Overflow,
+    #[serde(default)]
+    format: Format,
+}
+
+// numbers are given as strings so that big ones don't lose precision in JSON
+#[derive(Serialize, Deserialize, Debug)]
+struct CalculatorResult {
+    xor: String,
+    result: String,
+}
+
+trait PacketNumber: FromStr + BitXor<Output = Self> + Display + Default {
+    const NAME: &'static str;
+
+    fn cube(self, overflow: Overflow) -> Option<Self>;
+}
+
+macro_rules! impl_packet_number {
+    ($t:ty, $name:literal) => {
+        impl PacketNumber for $t {
+            const NAME: &'static str = $name;
+
+            fn cube(self, overflow: Overflow) -> Option<Self> {
+                match overflow {
+                    Overflow::Checked => self.checked_pow(3),
+                    Overflow::Wrapping => Some(self.wrapping_pow(3)),
+                    Overflow::Saturating => Some(self.saturating_pow(3)),
+                }
+            }
+        }
+    };
+}
+
+impl_packet_number!(i64, "i64");
+impl_packet_number!(u128, "u128");
+
+impl PacketNumber for BigInt {
+    const NAME: &'static str = "bigint";
+
+    fn cube(self, _overflow: Overflow) -> Option<Self> {
+        Some(self.pow(3))
+    }
+}
+
+async fn cube_the_bits(
+    Path(l_nums): Path<String>,
+    Query(calculator): Query<Calculator>,
+) -> Response {
+    let res = match calculator.number_type {
+        NumberType::I64 => xor_and_cube::<i64>(&l_nums, calculator.overflow),
+        NumberType::U128 => xor_and_cube::<u128>(&l_nums, calculator.overflow),
+        NumberType::BigInt => xor_and_cube::<BigInt>(&l_nums, calculator.overflow),
+    };
+
+    match (res, calculator.format) {
+        (Ok(res), Format::Text) => (StatusCode::OK, res.result).into_response(),
+        (Ok(res), Format::Json) => (StatusCode::OK, Json(res)).into_response(),
+        (Err(error), _) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
 }
 
-async fn cube_the_bits(Path(l_nums): Path<String>) -> (StatusCode, String) {
-    let res = l_nums
+fn xor_and_cube<T: PacketNumber>(
+    l_nums: &str,
+    overflow: Overflow,
+) -> Result<CalculatorResult, String> {
+    let xor = l_nums
         .split('/')
-        .map(|n| n.parse::<i32>().unwrap())
-        .fold(0, |acc, v| acc ^ v)
-        .pow(3);
+        .map(|n| {
+            n.parse::<T>()
+                .map_err(|_| format!("'{n}' is not a valid {}", T::NAME))
+        })
+        .try_fold(T::default(), |acc, v| v.map(|v| acc ^ v))?;
+    let xor_text = xor.to_string();
+    let result = xor
+        .cube(overflow)
+        .ok_or(format!("{xor_text} cubed overflows {}", T::NAME))?;
+
+    Ok(CalculatorResult {
+        xor: xor_text,
+        result: result.to_string(),
+    })
+}
+
+#[derive(Deserialize, Debug)]
+struct Evaluation {
+    expression: String,
+    #[serde(default)]
+    variables: HashMap<String, eval::Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EvaluationResult {
+    result: eval::Value,
+    steps: usize,
+}
 
-    (StatusCode::OK, res.to_string())
+async fn evaluate(Json(evaluation): Json<Evaluation>) -> Response {
+    match eval::evaluate(&evaluation.expression, evaluation.variables) {
+        Ok((result, steps)) => {
+            (StatusCode::OK, Json(EvaluationResult { result, steps })).into_response()
+        }
+        Err(error) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use axum::http::StatusCode;
     use axum_test::TestServer;
+    use serde_json::json;
 
     use super::*;
 
@@ -53,4 +193,114 @@ mod tests {
 
         response.assert_text(27.to_string());
     }
+
+    #[tokio::test]
+    async fn big_numbers() {
+        let app = get_day_1_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // 2^21 cubed doesn't fit in an i64.
+        // Send the request.
+        let response = server.get("/2097152").await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .get("/2097152")
+            .add_query_param("type", "bigint")
+            .add_query_param("format", "json")
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({"xor":"2097152","result":"9223372036854775808"}));
+
+        // Send the request.
+        let response = server
+            .get("/2097152")
+            .add_query_param("overflow", "saturating")
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_text(i64::MAX.to_string());
+    }
+
+    #[tokio::test]
+    async fn not_a_number() {
+        let app = get_day_1_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server.get("/4/-8").add_query_param("type", "u128").await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        response.assert_text("'-8' is not a valid u128");
+    }
+
+    #[tokio::test]
+    async fn eval() {
+        let app = get_day_1_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // The same rule as task 2.
+        // Send the request.
+        let response = server
+            .post("/eval")
+            .json(&json!({"expression": "xor(nums) ** 3", "variables": {"nums": [4, 5, 8, 10]}}))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        assert_eq!(
+            response.json::<EvaluationResult>().result,
+            eval::Value::Int(27)
+        );
+
+        // Send the request.
+        let response = server
+            .post("/eval")
+            .json(&json!({
+                "expression": "fold(map(nums, n => popcount(n)), 0, (acc, bits) => acc + bits)",
+                "variables": {"nums": [1, 3, 7, -1]}
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        assert_eq!(
+            response.json::<EvaluationResult>().result,
+            eval::Value::Int(70)
+        );
+
+        // Too many steps.
+        // Send the request.
+        let response = server
+            .post("/eval")
+            .json(&json!({
+                "expression": "fold(nums, 0, (a, b) => fold(nums, a, (c, d) => c + d))",
+                "variables": {"nums": vec![1; 1000]}
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // A chain of operators too deep to evaluate.
+        // Send the request.
+        let response = server
+            .post("/eval")
+            .json(&json!({"expression": vec!["1"; 2048].join("+")}))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("expression is nested too deeply");
+    }
 }
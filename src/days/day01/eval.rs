@@ -0,0 +1,522 @@
+//! Small expression language over integers and integer lists, for instance
+//! `xor(nums) ** 3` or `fold(nums, 0, (acc, n) => acc + popcount(n))`.
+//!
+//! Precedence, from the loosest: `|`, `^`, `&`, `<<` `>>`, `+` `-`, `*` `/` `%`,
+//! unary `-` `~`, then `**` (right associative).
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+const MAX_EXPRESSION_LENGTH: usize = 4096;
+/// Of the syntax tree, long chains of operators counting as much as nesting.
+const MAX_DEPTH: usize = 256;
+pub const MAX_STEPS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Value {
+    Int(i64),
+    List(Vec<i64>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    Arrow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+impl BinaryOp {
+    /// Operator, precedence and right associativity of a token.
+    fn from_token(token: &Token) -> Option<(BinaryOp, u8, bool)> {
+        let op = match token {
+            Token::Op("|") => (BinaryOp::Or, 1, false),
+            Token::Op("^") => (BinaryOp::Xor, 2, false),
+            Token::Op("&") => (BinaryOp::And, 3, false),
+            Token::Op("<<") => (BinaryOp::Shl, 4, false),
+            Token::Op(">>") => (BinaryOp::Shr, 4, false),
+            Token::Op("+") => (BinaryOp::Add, 5, false),
+            Token::Op("-") => (BinaryOp::Sub, 5, false),
+            Token::Op("*") => (BinaryOp::Mul, 6, false),
+            Token::Op("/") => (BinaryOp::Div, 6, false),
+            Token::Op("%") => (BinaryOp::Rem, 6, false),
+            Token::Op("**") => (BinaryOp::Pow, 8, true),
+            _ => return None,
+        };
+        Some(op)
+    }
+}
+
+const UNARY_PRECEDENCE: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Int(i64),
+    Var(String),
+    List(Vec<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Lambda(Vec<String>, Box<Expr>),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    const OPS: [&str; 14] = [
+        "**", "<<", ">>", "=>", "+", "-", "*", "/", "%", "&", "|", "^", "~", ",",
+    ];
+
+    let mut tokens = vec![];
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let n = rest[..len]
+                .parse()
+                .map_err(|_| format!("'{}' is too big", &rest[..len]))?;
+            tokens.push(Token::Int(n));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if let Some(bracket) = match c {
+            '(' => Some(Token::LParen),
+            ')' => Some(Token::RParen),
+            '[' => Some(Token::LBracket),
+            ']' => Some(Token::RBracket),
+            _ => None,
+        } {
+            tokens.push(bracket);
+            1
+        } else if let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(match *op {
+                "=>" => Token::Arrow,
+                "," => Token::Comma,
+                op => Token::Op(op),
+            });
+            op.len()
+        } else {
+            return Err(format!("unexpected character '{c}'"));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("expected {expected:?}, found {token:?}")),
+            None => Err(format!("expected {expected:?}, found the end")),
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("expression is nested too deeply".to_string());
+        }
+        Ok(())
+    }
+
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        self.enter()?;
+        let mut lhs = self.parse_unary()?;
+        let mut folded = 0;
+        while let Some((op, precedence, right_assoc)) = self.peek().and_then(BinaryOp::from_token) {
+            if precedence < min_precedence {
+                break;
+            }
+            // each operator folded puts the left-hand side one level deeper
+            self.enter()?;
+            folded += 1;
+            self.pos += 1;
+            let next_min = if right_assoc {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.parse_expr(next_min)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        self.depth -= 1 + folded;
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek() {
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            Some(Token::Op("~")) => UnaryOp::Not,
+            _ => return self.parse_primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(
+            op,
+            Box::new(self.parse_expr(UNARY_PRECEDENCE)?),
+        ))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Int(n)) => Ok(Expr::Int(n)),
+            Some(Token::Ident(name)) => match self.peek() {
+                Some(Token::LParen) => {
+                    self.pos += 1;
+                    let args = self.parse_list(Token::RParen)?;
+                    Ok(Expr::Call(name, args))
+                }
+                Some(Token::Arrow) => {
+                    self.pos += 1;
+                    Ok(Expr::Lambda(vec![name], Box::new(self.parse_expr(0)?)))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            Some(Token::LParen) => {
+                if let Some(params) = self.parse_lambda_params() {
+                    return Ok(Expr::Lambda(params, Box::new(self.parse_expr(0)?)));
+                }
+                let expr = self.parse_expr(0)?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::LBracket) => Ok(Expr::List(self.parse_list(Token::RBracket)?)),
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of the expression".to_string()),
+        }
+    }
+
+    /// `a, b) =>` after an opening parenthesis, nothing is consumed if it isn't one.
+    fn parse_lambda_params(&mut self) -> Option<Vec<String>> {
+        let start = self.pos;
+        let mut params = vec![];
+        loop {
+            match (self.next(), self.next()) {
+                (Some(Token::Ident(param)), Some(Token::Comma)) => params.push(param),
+                (Some(Token::Ident(param)), Some(Token::RParen)) => {
+                    params.push(param);
+                    if self.next() == Some(Token::Arrow) {
+                        return Some(params);
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.pos = start;
+        None
+    }
+
+    fn parse_list(&mut self, end: Token) -> Result<Vec<Expr>, String> {
+        let mut items = vec![];
+        if self.peek() == Some(&end) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(self.parse_expr(0)?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(token) if token == end => return Ok(items),
+                _ => return Err(format!("expected ',' or {end:?}")),
+            }
+        }
+    }
+}
+
+fn parse(input: &str) -> Result<Expr, String> {
+    if input.len() > MAX_EXPRESSION_LENGTH {
+        return Err(format!(
+            "expression is longer than {MAX_EXPRESSION_LENGTH} characters"
+        ));
+    }
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = parser.parse_expr(0)?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {token:?} after the expression"));
+    }
+    Ok(expr)
+}
+
+struct Evaluator {
+    steps: usize,
+    // variables of the request, then the parameters of the lambdas being called
+    scope: Vec<(String, Value)>,
+}
+
+impl Evaluator {
+    fn eval(&mut self, expr: &Expr) -> Result<Value, String> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(format!("evaluation exceeded {MAX_STEPS} steps"));
+        }
+
+        match expr {
+            Expr::Int(n) => Ok(Value::Int(*n)),
+            Expr::Var(name) => self
+                .scope
+                .iter()
+                .rev()
+                .find(|(var, _)| var == name)
+                .map(|(_, value)| value.clone())
+                .ok_or(format!("unknown variable '{name}'")),
+            Expr::List(items) => items
+                .iter()
+                .map(|item| self.eval_int(item))
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            Expr::Unary(op, expr) => {
+                let n = self.eval_int(expr)?;
+                match op {
+                    UnaryOp::Neg => n.checked_neg().ok_or(overflow()),
+                    UnaryOp::Not => Ok(!n),
+                }
+                .map(Value::Int)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval_int(lhs)?;
+                let rhs = self.eval_int(rhs)?;
+                apply(*op, lhs, rhs).map(Value::Int)
+            }
+            Expr::Call(name, args) => self.call(name, args),
+            Expr::Lambda(..) => Err("a lambda can only be given to map or fold".to_string()),
+        }
+    }
+
+    fn eval_int(&mut self, expr: &Expr) -> Result<i64, String> {
+        match self.eval(expr)? {
+            Value::Int(n) => Ok(n),
+            Value::List(_) => Err("expected an integer, found a list".to_string()),
+        }
+    }
+
+    fn eval_list(&mut self, expr: &Expr) -> Result<Vec<i64>, String> {
+        match self.eval(expr)? {
+            Value::List(list) => Ok(list),
+            Value::Int(_) => Err("expected a list, found an integer".to_string()),
+        }
+    }
+
+    fn call_lambda(&mut self, lambda: &Expr, args: Vec<i64>) -> Result<i64, String> {
+        let Expr::Lambda(params, body) = lambda else {
+            return Err("expected a lambda like 'x => x * 2'".to_string());
+        };
+        if params.len() != args.len() {
+            return Err(format!(
+                "the lambda takes {} parameters, {} given",
+                params.len(),
+                args.len()
+            ));
+        }
+        let scope_len = self.scope.len();
+        self.scope
+            .extend(params.iter().cloned().zip(args.into_iter().map(Value::Int)));
+        let res = self.eval_int(body);
+        self.scope.truncate(scope_len);
+        res
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> Result<Value, String> {
+        let arity = match name {
+            "xor" | "and" | "or" | "sum" | "min" | "max" | "len" | "popcount" => 1,
+            "pow" | "shl" | "shr" | "map" => 2,
+            "fold" => 3,
+            _ => return Err(format!("unknown function '{name}'")),
+        };
+        if args.len() != arity {
+            return Err(format!(
+                "{name} takes {arity} arguments, {} given",
+                args.len()
+            ));
+        }
+
+        let res = match name {
+            "xor" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .fold(0, |acc, n| acc ^ n),
+            "and" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .fold(!0, |acc, n| acc & n),
+            "or" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .fold(0, |acc, n| acc | n),
+            "sum" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .try_fold(0i64, |acc, n| acc.checked_add(n))
+                .ok_or(overflow())?,
+            "min" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .min()
+                .ok_or("min of an empty list")?,
+            "max" => self
+                .eval_list(&args[0])?
+                .into_iter()
+                .max()
+                .ok_or("max of an empty list")?,
+            "len" => self.eval_list(&args[0])?.len() as i64,
+            "popcount" => self.eval_int(&args[0])?.count_ones() as i64,
+            "pow" | "shl" | "shr" => {
+                let op = match name {
+                    "pow" => BinaryOp::Pow,
+                    "shl" => BinaryOp::Shl,
+                    _ => BinaryOp::Shr,
+                };
+                let lhs = self.eval_int(&args[0])?;
+                let rhs = self.eval_int(&args[1])?;
+                apply(op, lhs, rhs)?
+            }
+            "map" => {
+                let list = self.eval_list(&args[0])?;
+                return list
+                    .into_iter()
+                    .map(|n| self.call_lambda(&args[1], vec![n]))
+                    .collect::<Result<_, _>>()
+                    .map(Value::List);
+            }
+            _ => {
+                let list = self.eval_list(&args[0])?;
+                let init = self.eval_int(&args[1])?;
+                list.into_iter()
+                    .try_fold(init, |acc, n| self.call_lambda(&args[2], vec![acc, n]))?
+            }
+        };
+        Ok(Value::Int(res))
+    }
+}
+
+fn overflow() -> String {
+    "integer overflow".to_string()
+}
+
+fn apply(op: BinaryOp, lhs: i64, rhs: i64) -> Result<i64, String> {
+    let shift = || u32::try_from(rhs).map_err(|_| format!("invalid shift amount {rhs}"));
+    match op {
+        BinaryOp::Or => Ok(lhs | rhs),
+        BinaryOp::Xor => Ok(lhs ^ rhs),
+        BinaryOp::And => Ok(lhs & rhs),
+        BinaryOp::Shl => lhs
+            .checked_shl(shift()?)
+            .ok_or(format!("invalid shift amount {rhs}")),
+        BinaryOp::Shr => lhs
+            .checked_shr(shift()?)
+            .ok_or(format!("invalid shift amount {rhs}")),
+        BinaryOp::Add => lhs.checked_add(rhs).ok_or(overflow()),
+        BinaryOp::Sub => lhs.checked_sub(rhs).ok_or(overflow()),
+        BinaryOp::Mul => lhs.checked_mul(rhs).ok_or(overflow()),
+        BinaryOp::Div | BinaryOp::Rem if rhs == 0 => Err("division by zero".to_string()),
+        BinaryOp::Div => lhs.checked_div(rhs).ok_or(overflow()),
+        BinaryOp::Rem => lhs.checked_rem(rhs).ok_or(overflow()),
+        BinaryOp::Pow => {
+            let exponent = u32::try_from(rhs).map_err(|_| format!("invalid exponent {rhs}"))?;
+            lhs.checked_pow(exponent).ok_or(overflow())
+        }
+    }
+}
+
+/// Value of the expression and the number of evaluation steps it took.
+pub fn evaluate(
+    expression: &str,
+    variables: HashMap<String, Value>,
+) -> Result<(Value, usize), String> {
+    let expr = parse(expression)?;
+    let mut evaluator = Evaluator {
+        steps: 0,
+        scope: variables.into_iter().collect(),
+    };
+    let value = evaluator.eval(&expr)?;
+    Ok((value, evaluator.steps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<Value, String> {
+        evaluate(expression, HashMap::new()).map(|(value, _)| value)
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(Value::Int(7)));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(Value::Int(9)));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(Value::Int(512)));
+        assert_eq!(eval("-2 ** 2"), Ok(Value::Int(-4)));
+        assert_eq!(eval("1 | 6 ^ 3 & 5"), Ok(Value::Int(7)));
+        assert_eq!(eval("1 << 2 + 1"), Ok(Value::Int(8)));
+        assert_eq!(
+            eval("map([1, 2, 3], x => x * 2)"),
+            Ok(Value::List(vec![2, 4, 6]))
+        );
+        assert!(eval("1 +").is_err());
+        assert!(eval("[1, 2] + 1").is_err());
+    }
+
+    #[test]
+    fn test_long_chains() {
+        let sum = vec!["1"; 250].join("+");
+        assert_eq!(eval(&sum), Ok(Value::Int(250)));
+
+        let sum = vec!["1"; 2048].join("+");
+        assert_eq!(
+            eval(&sum),
+            Err("expression is nested too deeply".to_string())
+        );
+    }
+}
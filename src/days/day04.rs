@@ -1,44 +1,177 @@
-use axum::http::StatusCode;
-use axum::routing::post;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use axum::extract::{FromRef, State};
+use axum::http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
 use axum::{Json, Router};
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::db;
+use crate::db::structs::MyState;
+
+mod announcements;
+mod roster;
+mod stats;
+
+use announcements::Templates;
+
+#[derive(Clone)]
+struct Day4State {
+    db: MyState,
+    templates: Templates,
+}
+
+impl FromRef<Day4State> for MyState {
+    fn from_ref(state: &Day4State) -> Self {
+        state.db.clone()
+    }
+}
 
-pub fn get_day_4_router() -> Router {
+impl FromRef<Day4State> for Templates {
+    fn from_ref(state: &Day4State) -> Self {
+        state.templates.clone()
+    }
+}
+
+pub fn get_day_4_router(db: MyState) -> Router {
     Router::new()
         .route("/strength", post(strength))
         .route("/contest", post(contest))
+        .route("/stats", post(stats::stats))
+        .route(
+            "/reindeer",
+            get(roster::list_reindeer).post(roster::create_reindeer),
+        )
+        .route(
+            "/reindeer/:id",
+            get(roster::get_reindeer)
+                .put(roster::update_reindeer)
+                .delete(roster::delete_reindeer),
+        )
+        .route("/history/:name", get(roster::history))
+        .route("/templates/:locale", get(announcements::list_templates))
+        .route(
+            "/templates/:locale/:category",
+            put(announcements::register_template),
+        )
+        .with_state(Day4State {
+            db,
+            templates: Templates::default(),
+        })
 }
 
-#[derive(Deserialize, Debug)]
+/// The numbers are kept as sent, so the announcements repeat them verbatim.
+#[derive(Deserialize, Serialize, Debug)]
 struct Reindeer {
     name: String,
-    strength: f32,
-    speed: f32,
-    height: f32,
-    antler_width: f32,
-    snow_magic_power: f32,
+    strength: Number,
+    speed: Number,
+    height: Number,
+    antler_width: Number,
+    snow_magic_power: Number,
     favorite_food: String,
     #[serde(rename = "cAnD13s_3ATeN-yesT3rdAy")]
-    candies_eaten_yesterday: f32,
+    candies_eaten_yesterday: Number,
 }
 #[derive(Deserialize, Debug)]
 struct ReindeerSimple {
     strength: u32,
 }
 
-#[derive(Serialize, Debug)]
-struct ReindeerResult {
+/// Either the plain list of reindeer of the original contest,
+/// or a contest definition with its own categories.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ContestRequest {
+    Classic(Vec<Reindeer>),
+    Custom(ContestDefinition),
+}
+
+#[derive(Deserialize, Debug)]
+struct ContestDefinition {
+    #[serde(default)]
+    reindeer: Vec<Map<String, Value>>,
+    /// Stored reindeer joining the ones given in the request.
+    roster: Option<roster::RosterSelection>,
+    #[serde(default = "classic_categories")]
+    categories: Vec<Category>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Category {
+    name: String,
+    #[serde(flatten)]
+    scoring: Scoring,
+    /// Applied in order when scores are equal, the input order decides last.
+    #[serde(default)]
+    tie_break: Vec<TieBreak>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "aggregation", rename_all = "lowercase")]
+enum Scoring {
+    Max { field: String },
+    Min { field: String },
+    Weighted { weights: HashMap<String, f64> },
+}
+
+#[derive(Deserialize, Debug)]
+struct TieBreak {
+    field: String,
+    #[serde(default)]
+    order: SortOrder,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Placing {
+    rank: usize,
     name: String,
-    description: String,
+    score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Leaderboard {
+    category: String,
+    ranking: Vec<Placing>,
 }
 
 #[derive(Serialize, Debug)]
-struct ContestResult {
-    fastest: String,
-    tallest: String,
-    magician: String,
-    consumer: String,
+struct CustomContestResult {
+    leaderboards: Vec<Leaderboard>,
+    /// The winner of each category with a template, in the negotiated locale if it has one.
+    #[serde(flatten)]
+    announcements: Map<String, Value>,
+}
+
+fn classic_categories() -> Vec<Category> {
+    [
+        ("fastest", "speed"),
+        ("tallest", "height"),
+        ("magician", "snow_magic_power"),
+        ("consumer", "cAnD13s_3ATeN-yesT3rdAy"),
+    ]
+    .into_iter()
+    .map(|(name, field)| Category {
+        name: name.to_string(),
+        scoring: Scoring::Max {
+            field: field.to_string(),
+        },
+        tie_break: vec![],
+    })
+    .collect()
 }
 
 async fn strength(Json(reindeer_list): Json<Vec<ReindeerSimple>>) -> (StatusCode, String) {
@@ -49,44 +182,218 @@ async fn strength(Json(reindeer_list): Json<Vec<ReindeerSimple>>) -> (StatusCode
     (StatusCode::OK, sum.to_string())
 }
 
-async fn contest(Json(reindeer_list): Json<Vec<Reindeer>>) -> Json<ContestResult> {
-    let fastest = get_reindeer_result(&reindeer_list, |r| r.speed);
-    let fastest = format!(
-        "Speeding past the finish line with a strength of {} is {}",
-        fastest.strength, fastest.name
-    );
-    let tallest = get_reindeer_result(&reindeer_list, |r| r.height);
-    let tallest = format!(
-        "{} is standing tall with his {} cm wide antlers",
-        tallest.name, tallest.antler_width
-    );
-    let magician = get_reindeer_result(&reindeer_list, |r| r.snow_magic_power);
-    let magician = format!(
-        "{} could blast you away with a snow magic power of {}",
-        magician.name, magician.snow_magic_power
-    );
-    let consumer = get_reindeer_result(&reindeer_list, |r| r.candies_eaten_yesterday);
-    let consumer = format!(
-        "{} ate lots of candies, but also some {}",
-        consumer.name, consumer.favorite_food
+async fn contest(
+    State(db): State<MyState>,
+    State(templates): State<Templates>,
+    headers: HeaderMap,
+    Json(request): Json<ContestRequest>,
+) -> Response {
+    let locale = templates.negotiate(
+        headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok()),
     );
 
-    Json(ContestResult {
-        fastest,
-        tallest,
-        magician,
-        consumer,
-    })
+    let response = match request {
+        ContestRequest::Classic(reindeer_list) => {
+            if reindeer_list.is_empty() {
+                return (StatusCode::BAD_REQUEST, "No reindeer in the contest").into_response();
+            }
+            let entries: Vec<Map<String, Value>> = reindeer_list
+                .iter()
+                .map(|reindeer| {
+                    serde_json::from_value(serde_json::to_value(reindeer).unwrap()).unwrap()
+                })
+                .collect();
+            if let Some(name) = duplicate_name(&entries) {
+                return (StatusCode::BAD_REQUEST, format!("{name} entered twice")).into_response();
+            }
+            // not recorded, so that the original contest works without a database
+            let leaderboards = rank_all(&entries, &classic_categories());
+            Json(announce(&templates, &locale, &entries, &leaderboards)).into_response()
+        }
+        ContestRequest::Custom(definition) => {
+            let mut entries = definition.reindeer;
+            if let Some(selection) = &definition.roster {
+                match roster::select_roster(db.clone(), selection).await {
+                    Ok(stored) => entries.extend(stored),
+                    Err(error) => return error.into_response(),
+                }
+            }
+            if entries.is_empty() {
+                return (StatusCode::BAD_REQUEST, "No reindeer in the contest").into_response();
+            }
+            if entries
+                .iter()
+                .any(|reindeer| !reindeer.get("name").is_some_and(Value::is_string))
+            {
+                return (StatusCode::BAD_REQUEST, "Every reindeer needs a name").into_response();
+            }
+            if let Some(name) = duplicate_name(&entries) {
+                return (StatusCode::BAD_REQUEST, format!("{name} entered twice")).into_response();
+            }
+            let leaderboards = rank_all(&entries, &definition.categories);
+            let announcements = announce(&templates, &locale, &entries, &leaderboards);
+            if let Err(error) = record_contest(db, entries, &leaderboards).await {
+                return (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response();
+            }
+
+            Json(CustomContestResult {
+                leaderboards,
+                announcements,
+            })
+            .into_response()
+        }
+    };
+
+    ([(CONTENT_LANGUAGE, locale)], response).into_response()
+}
+
+/// The first name given to more than one reindeer, the history telling them apart by name.
+fn duplicate_name(entries: &[Map<String, Value>]) -> Option<&str> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter_map(|reindeer| reindeer.get("name")?.as_str())
+        .find(|name| !seen.insert(*name))
+}
+
+async fn record_contest(
+    db: MyState,
+    entries: Vec<Map<String, Value>>,
+    leaderboards: &[Leaderboard],
+) -> Result<i32, sqlx::Error> {
+    let entries = entries
+        .into_iter()
+        .map(|mut fields| {
+            let name = fields.remove("name").unwrap().as_str().unwrap().to_string();
+            (name, fields)
+        })
+        .collect();
+    let placings = leaderboards
+        .iter()
+        .flat_map(|leaderboard| {
+            leaderboard.ranking.iter().map(|placing| {
+                (
+                    placing.name.clone(),
+                    leaderboard.category.clone(),
+                    placing.rank as i32,
+                    placing.score,
+                )
+            })
+        })
+        .collect();
+
+    db::methods::insert_contest(db, entries, placings).await
+}
+
+fn rank_all(reindeer_list: &[Map<String, Value>], categories: &[Category]) -> Vec<Leaderboard> {
+    categories
+        .iter()
+        .map(|category| Leaderboard {
+            category: category.name.clone(),
+            ranking: rank(reindeer_list, category),
+        })
+        .collect()
+}
+
+/// Announces the first placed reindeer of every category.
+fn announce(
+    templates: &Templates,
+    locale: &str,
+    reindeer_list: &[Map<String, Value>],
+    leaderboards: &[Leaderboard],
+) -> Map<String, Value> {
+    let winners = leaderboards.iter().filter_map(|leaderboard| {
+        let winner = leaderboard.ranking.first()?;
+        let reindeer = reindeer_list
+            .iter()
+            .find(|reindeer| reindeer["name"] == winner.name.as_str())?;
+        Some((leaderboard.category.as_str(), reindeer))
+    });
+
+    templates.announce(locale, winners)
+}
+
+fn metric(reindeer: &Map<String, Value>, field: &str) -> Option<f64> {
+    reindeer.get(field).and_then(Value::as_f64)
+}
+
+fn score(reindeer: &Map<String, Value>, scoring: &Scoring) -> Option<f64> {
+    match scoring {
+        Scoring::Max { field } | Scoring::Min { field } => metric(reindeer, field),
+        Scoring::Weighted { weights } => weights
+            .iter()
+            .map(|(field, weight)| metric(reindeer, field).map(|value| value * weight))
+            .sum(),
+    }
+}
+
+/// Present values first, then numbers or strings compared with each other.
+fn compare_values(a: Option<&Value>, b: Option<&Value>, order: SortOrder) -> Ordering {
+    let ordering = match (a, b) {
+        (Some(Value::Number(a)), Some(Value::Number(b))) => {
+            OrderedFloat(a.as_f64().unwrap()).cmp(&OrderedFloat(b.as_f64().unwrap()))
+        }
+        (Some(Value::String(a)), Some(Value::String(b))) => a.cmp(b),
+        (Some(_), None) => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        _ => return Ordering::Equal,
+    };
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
 }
 
-fn get_reindeer_result<F>(reindeer_list: &[Reindeer], key_fn: F) -> &Reindeer
-where
-    F: Fn(&Reindeer) -> f32,
-{
-    reindeer_list
+fn rank(reindeer_list: &[Map<String, Value>], category: &Category) -> Vec<Placing> {
+    let order = match category.scoring {
+        Scoring::Min { .. } => SortOrder::Asc,
+        Scoring::Max { .. } | Scoring::Weighted { .. } => SortOrder::Desc,
+    };
+    let compare = |a: &(&Map<String, Value>, Option<f64>),
+                   b: &(&Map<String, Value>, Option<f64>)| {
+        compare_values(
+            a.1.map(Value::from).as_ref(),
+            b.1.map(Value::from).as_ref(),
+            order,
+        )
+        .then_with(|| {
+            category
+                .tie_break
+                .iter()
+                .map(|tie_break| {
+                    compare_values(
+                        a.0.get(&tie_break.field),
+                        b.0.get(&tie_break.field),
+                        tie_break.order,
+                    )
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        })
+    };
+
+    let mut scored: Vec<_> = reindeer_list
         .iter()
-        .max_by_key(|&r| OrderedFloat(key_fn(r)))
-        .unwrap()
+        .map(|reindeer| (reindeer, score(reindeer, &category.scoring)))
+        .collect();
+    scored.sort_by(compare);
+
+    let mut placings: Vec<Placing> = Vec::with_capacity(scored.len());
+    for (i, entry) in scored.iter().enumerate() {
+        // reindeer that can't be told apart share the same rank
+        let rank = match placings.last() {
+            Some(previous) if compare(&scored[i - 1], entry).is_eq() => previous.rank,
+            _ => i + 1,
+        };
+        placings.push(Placing {
+            rank,
+            name: entry.0["name"].as_str().unwrap().to_string(),
+            score: entry.1,
+        });
+    }
+    placings
 }
 
 #[cfg(test)]
@@ -95,11 +402,20 @@ mod tests {
     use axum_test::TestServer;
     use serde_json::json;
 
+    use crate::db::testing::{unconnected, TestDb};
+
     use super::*;
 
+    async fn setup_test_server() -> (TestServer, TestDb) {
+        let test_db = TestDb::new().await;
+        let app = get_day_4_router(test_db.db.clone());
+
+        (TestServer::new(app).unwrap(), test_db)
+    }
+
     #[tokio::test]
     async fn task1() {
-        let app = get_day_4_router();
+        let app = get_day_4_router(unconnected());
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -122,7 +438,7 @@ mod tests {
 
     #[tokio::test]
     async fn task2() {
-        let app = get_day_4_router();
+        let app = get_day_4_router(unconnected());
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -158,9 +474,352 @@ mod tests {
 
         response.assert_json(&json!({
           "fastest": "Speeding past the finish line with a strength of 5 is Dasher",
-          "tallest": "Dasher is standing tall with his 36 cm wide antlers",
+          "tallest": "Dasher is standing tall with their 36 cm wide antlers",
           "magician": "Dasher could blast you away with a snow magic power of 9001",
           "consumer": "Dancer ate lots of candies, but also some grass"
         }));
     }
+
+    #[tokio::test]
+    async fn custom_contest() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .json(&json!({
+                "reindeer": [
+                    { "name": "Dasher", "strength": 5, "speed": 50.4 },
+                    { "name": "Dancer", "strength": 6, "speed": 48.2 },
+                    { "name": "Prancer", "strength": 6, "speed": 30 },
+                    { "name": "Vixen", "strength": 4 }
+                ],
+                "categories": [
+                    { "name": "slowest", "aggregation": "min", "field": "speed" },
+                    {
+                        "name": "overall",
+                        "aggregation": "weighted",
+                        "weights": { "strength": 10, "speed": 0 },
+                        "tie_break": [{ "field": "name", "order": "asc" }]
+                    }
+                ]
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "leaderboards": [
+            {
+              "category": "slowest",
+              "ranking": [
+                { "rank": 1, "name": "Prancer", "score": 30.0 },
+                { "rank": 2, "name": "Dancer", "score": 48.2 },
+                { "rank": 3, "name": "Dasher", "score": 50.4 },
+                { "rank": 4, "name": "Vixen", "score": null }
+              ]
+            },
+            {
+              "category": "overall",
+              "ranking": [
+                { "rank": 1, "name": "Dancer", "score": 60.0 },
+                { "rank": 2, "name": "Prancer", "score": 60.0 },
+                { "rank": 3, "name": "Dasher", "score": 50.0 },
+                { "rank": 4, "name": "Vixen", "score": null }
+              ]
+            }
+          ]
+        }));
+    }
+
+    #[tokio::test]
+    async fn classic_leaderboards() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .json(&json!({
+                "reindeer": [
+                    {
+                        "name": "Dasher",
+                        "strength": 5,
+                        "speed": 50.4,
+                        "height": 80,
+                        "antler_width": 36,
+                        "snow_magic_power": 9001,
+                        "favorite_food": "hay",
+                        "cAnD13s_3ATeN-yesT3rdAy": 2
+                    },
+                    {
+                        "name": "Dancer",
+                        "strength": 6,
+                        "speed": 48.2,
+                        "height": 65,
+                        "antler_width": 37,
+                        "snow_magic_power": 4004,
+                        "favorite_food": "grass",
+                        "cAnD13s_3ATeN-yesT3rdAy": 5
+                    }
+                ]
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let result = response.json::<Value>();
+        assert_eq!(
+            result["consumer"],
+            "Dancer ate lots of candies, but also some grass"
+        );
+        assert_eq!(result["leaderboards"].as_array().unwrap().len(), 4);
+        assert_eq!(result["leaderboards"][0]["ranking"][0]["name"], "Dasher");
+    }
+
+    #[tokio::test]
+    async fn localized_announcements() {
+        let app = get_day_4_router(unconnected());
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let reindeer = json!([
+            {
+                "name": "Dasher",
+                "strength": 5,
+                "speed": 50.4,
+                "height": 80,
+                "antler_width": 36.5,
+                "snow_magic_power": 9001,
+                "favorite_food": "hay",
+                "cAnD13s_3ATeN-yesT3rdAy": 2
+            }
+        ]);
+
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .add_header(
+                ACCEPT_LANGUAGE,
+                "es, fr-CA;q=0.8, en;q=0.5".parse().unwrap(),
+            )
+            .json(&reindeer)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        assert_eq!(response.header(CONTENT_LANGUAGE), "fr");
+
+        response.assert_json(&json!({
+          "fastest": "Avec une force de 5, Dasher franchit la ligne d'arrivée en premier",
+          "tallest": "Dasher se dresse fièrement avec des bois de 36.5 cm de large",
+          "magician": "Dasher pourrait vous balayer avec une magie des neiges de 9001",
+          "consumer": "Dasher a mangé beaucoup de bonbons, mais aussi un peu de hay"
+        }));
+
+        // Send the request.
+        let response = server
+            .put("/templates/es/fastest")
+            .json(&json!({ "template": "¡{name} gana con {strength} de fuerza!" }))
+            .await;
+
+        response.assert_status(StatusCode::CREATED);
+
+        // Send the request.
+        let response = server
+            .put("/templates/es/slowest")
+            .json(&json!({ "template": "{name} se toma su tiempo {" }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .add_header(ACCEPT_LANGUAGE, "es".parse().unwrap())
+            .json(&reindeer)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "fastest": "¡Dasher gana con 5 de fuerza!",
+          "tallest": "Dasher is standing tall with their 36.5 cm wide antlers",
+          "magician": "Dasher could blast you away with a snow magic power of 9001",
+          "consumer": "Dasher ate lots of candies, but also some hay"
+        }));
+
+        // Send the request.
+        let response = server.get("/templates/es").await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({ "fastest": "¡{name} gana con {strength} de fuerza!" }));
+    }
+
+    #[tokio::test]
+    async fn roster() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+
+        // Send the request.
+        let response = server
+            .post("/reindeer")
+            .json(&json!({ "name": "Dasher", "strength": 5, "speed": 50.4 }))
+            .await;
+
+        response.assert_status(StatusCode::CREATED);
+
+        let dasher = response.json::<Value>()["id"].as_i64().unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/reindeer")
+            .json(&json!({ "name": "Dasher", "strength": 1 }))
+            .await;
+
+        response.assert_status(StatusCode::CONFLICT);
+
+        // Send the request.
+        let response = server
+            .post("/reindeer")
+            .json(&json!({ "name": "Vixen", "strength": 7, "speed": 20 }))
+            .await;
+
+        response.assert_status(StatusCode::CREATED);
+
+        // Send the request.
+        let response = server
+            .put(&format!("/reindeer/{dasher}"))
+            .json(&json!({ "name": "Dasher", "strength": 6, "speed": 52 }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response
+            .assert_json(&json!({ "id": dasher, "name": "Dasher", "strength": 6, "speed": 52 }));
+
+        // Send the request.
+        let response = server
+            .put(&format!("/reindeer/{dasher}"))
+            .json(&json!({ "name": "Vixen", "strength": 6 }))
+            .await;
+
+        response.assert_status(StatusCode::CONFLICT);
+
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .json(&json!({
+                "reindeer": [{ "name": "Dasher", "speed": 40 }],
+                "roster": {},
+                "categories": [{ "name": "fastest", "aggregation": "max", "field": "speed" }]
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("Dasher entered twice");
+
+        // Only the stored reindeer faster than 30.
+        // Send the request.
+        let response = server
+            .post("/contest")
+            .json(&json!({
+                "reindeer": [{ "name": "Comet", "speed": 40 }],
+                "roster": { "filter": [{ "field": "speed", "min": 30 }] },
+                "categories": [{ "name": "fastest", "aggregation": "max", "field": "speed" }]
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "leaderboards": [
+            {
+              "category": "fastest",
+              "ranking": [
+                { "rank": 1, "name": "Dasher", "score": 52.0 },
+                { "rank": 2, "name": "Comet", "score": 40.0 }
+              ]
+            }
+          ],
+          "fastest": "Speeding past the finish line with a strength of 6 is Dasher"
+        }));
+
+        // Send the request.
+        let response = server.get("/history/Dasher").await;
+
+        response.assert_status(StatusCode::OK);
+
+        let history = response.json::<Value>();
+        assert_eq!(history["stats"], json!({ "strength": 6, "speed": 52 }));
+        assert_eq!(history["contests"].as_array().unwrap().len(), 1);
+        assert_eq!(
+            history["contests"][0]["placings"],
+            json!([{ "category": "fastest", "rank": 1, "score": 52.0 }])
+        );
+
+        // Send the request.
+        let response = server.delete(&format!("/reindeer/{dasher}")).await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server.get(&format!("/reindeer/{dasher}")).await;
+
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn stats() {
+        let app = get_day_4_router(unconnected());
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/stats")
+            .add_query_param("buckets", 2)
+            .json(&json!([
+                { "name": "Dasher", "strength": 5, "speed": 50.4 },
+                { "name": "Dancer", "strength": 6 },
+                { "name": "Prancer", "strength": 4, "speed": "fast" },
+                { "name": "Vixen", "strength": 7 }
+            ]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let stats = response.json::<Value>();
+        assert_eq!(
+            stats["strength"],
+            json!({
+                "count": 4,
+                "sum": 22.0,
+                "mean": 5.5,
+                "median": 5.5,
+                "stddev": 1.25_f64.sqrt(),
+                "min": { "value": 4.0, "names": ["Prancer"] },
+                "max": { "value": 7.0, "names": ["Vixen"] },
+                "histogram": [
+                    { "from": 4.0, "to": 5.5, "count": 2 },
+                    { "from": 5.5, "to": 7.0, "count": 2 }
+                ]
+            })
+        );
+        assert_eq!(stats["speed"]["count"], 1);
+        assert_eq!(stats["height"]["count"], 0);
+        assert_eq!(stats["height"]["mean"], Value::Null);
+
+        // Send the request.
+        let response = server
+            .post("/stats")
+            .add_query_param("buckets", 0)
+            .json(&json!([]))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const MAX_TEMPLATE_LENGTH: usize = 1024;
+
+/// Built-in template sets, keyed by locale then by category.
+const BUILT_IN: [(&str, [(&str, &str); 4]); 3] = [
+    (
+        "en",
+        [
+            (
+                "fastest",
+                "Speeding past the finish line with a strength of {strength} is {name}",
+            ),
+            (
+                "tallest",
+                "{name} is standing tall with their {antler_width} cm wide antlers",
+            ),
+            (
+                "magician",
+                "{name} could blast you away with a snow magic power of {snow_magic_power}",
+            ),
+            (
+                "consumer",
+                "{name} ate lots of candies, but also some {favorite_food}",
+            ),
+        ],
+    ),
+    (
+        "fr",
+        [
+            (
+                "fastest",
+                "Avec une force de {strength}, {name} franchit la ligne d'arrivée en premier",
+            ),
+            (
+                "tallest",
+                "{name} se dresse fièrement avec des bois de {antler_width} cm de large",
+            ),
+            (
+                "magician",
+                "{name} pourrait vous balayer avec une magie des neiges de {snow_magic_power}",
+            ),
+            (
+                "consumer",
+                "{name} a mangé beaucoup de bonbons, mais aussi un peu de {favorite_food}",
+            ),
+        ],
+    ),
+    (
+        "de",
+        [
+            (
+                "fastest",
+                "Mit einer Stärke von {strength} rast {name} über die Ziellinie",
+            ),
+            (
+                "tallest",
+                "{name} steht stolz mit einem {antler_width} cm breiten Geweih da",
+            ),
+            (
+                "magician",
+                "{name} könnte dich mit einer Schneemagie von {snow_magic_power} wegpusten",
+            ),
+            (
+                "consumer",
+                "{name} hat viele Süßigkeiten gegessen, aber auch etwas {favorite_food}",
+            ),
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(String),
+}
+
+/// A sentence where `{field}` is replaced by the field of the winning reindeer.
+/// `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone)]
+pub struct Template {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        if source.len() > MAX_TEMPLATE_LENGTH {
+            return Err(format!(
+                "Template is longer than {MAX_TEMPLATE_LENGTH} bytes"
+            ));
+        }
+
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => text.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => text.push('}'),
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, '{')) | None => {
+                                return Err(format!("Unclosed placeholder at {position}"))
+                            }
+                            Some((_, c)) => field.push(c),
+                        }
+                    }
+                    if field.is_empty() {
+                        return Err(format!("Empty placeholder at {position}"));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(format!("Unmatched '}}' at {position}")),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Template {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// `None` when a placeholder has no printable value on this reindeer.
+    pub fn render(&self, reindeer: &Map<String, Value>) -> Option<String> {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => Some(text.clone()),
+                Part::Field(field) => match reindeer.get(field)? {
+                    Value::String(value) => Some(value.clone()),
+                    Value::Number(value) => Some(value.to_string()),
+                    Value::Bool(value) => Some(value.to_string()),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+}
+
+/// Announcement templates registered per locale and category.
+#[derive(Clone)]
+pub struct Templates(Arc<RwLock<HashMap<String, HashMap<String, Template>>>>);
+
+impl Default for Templates {
+    fn default() -> Self {
+        let sets = BUILT_IN
+            .iter()
+            .map(|(locale, templates)| {
+                let templates = templates
+                    .iter()
+                    .map(|(category, source)| {
+                        (category.to_string(), Template::parse(source).unwrap())
+                    })
+                    .collect();
+                (locale.to_string(), templates)
+            })
+            .collect();
+
+        Templates(Arc::new(RwLock::new(sets)))
+    }
+}
+
+impl Templates {
+    /// Picks the preferred locale of an `Accept-Language` header that has templates,
+    /// falling back from `fr-CA` to `fr`, and to the default locale otherwise.
+    pub fn negotiate(&self, accept_language: Option<&str>) -> String {
+        let sets = self.0.read().unwrap();
+        let mut ranges: Vec<(String, f32)> = accept_language
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|range| {
+                let mut parameters = range.split(';');
+                let tag = parameters.next()?.trim().to_lowercase();
+                let quality = parameters
+                    .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                    .find_map(|quality| quality.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // stable, so equal qualities keep the order of the header
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges
+            .into_iter()
+            .find_map(|(tag, _)| {
+                if tag == "*" {
+                    return Some(DEFAULT_LOCALE.to_string());
+                }
+                let mut tag = tag.as_str();
+                loop {
+                    if sets.contains_key(tag) {
+                        return Some(tag.to_string());
+                    }
+                    tag = &tag[..tag.rfind('-')?];
+                }
+            })
+            .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+    }
+
+    /// Renders the template of each category in the locale, or else in the default locale.
+    pub fn announce<'a>(
+        &self,
+        locale: &str,
+        winners: impl IntoIterator<Item = (&'a str, &'a Map<String, Value>)>,
+    ) -> Map<String, Value> {
+        let sets = self.0.read().unwrap();
+        let template = |category: &str| {
+            [locale, DEFAULT_LOCALE]
+                .iter()
+                .find_map(|locale| sets.get(*locale)?.get(category))
+        };
+
+        winners
+            .into_iter()
+            .filter_map(|(category, winner)| {
+                let sentence = template(category)?.render(winner)?;
+                Some((category.to_string(), Value::String(sentence)))
+            })
+            .collect()
+    }
+}
+
+fn valid_locale(locale: &str) -> bool {
+    !locale.is_empty()
+        && locale.len() <= 35
+        && locale
+            .split('-')
+            .all(|subtag| !subtag.is_empty() && subtag.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct TemplateDefinition {
+    template: String,
+}
+
+pub async fn list_templates(
+    Path(locale): Path<String>,
+    State(templates): State<Templates>,
+) -> Response {
+    let sets = templates.0.read().unwrap();
+    match sets.get(&locale.to_lowercase()) {
+        Some(set) => Json(
+            set.iter()
+                .map(|(category, template)| (category.clone(), template.source.clone()))
+                .collect::<BTreeMap<_, _>>(),
+        )
+        .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn register_template(
+    Path((locale, category)): Path<(String, String)>,
+    State(templates): State<Templates>,
+    Json(definition): Json<TemplateDefinition>,
+) -> Response {
+    let locale = locale.to_lowercase();
+    if !valid_locale(&locale) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("'{locale}' is not a valid language tag"),
+        )
+            .into_response();
+    }
+    let template = match Template::parse(&definition.template) {
+        Ok(template) => template,
+        Err(error) => return (StatusCode::BAD_REQUEST, error).into_response(),
+    };
+
+    let replaced = templates
+        .0
+        .write()
+        .unwrap()
+        .entry(locale)
+        .or_default()
+        .insert(category, template)
+        .is_some();
+
+    let status = if replaced {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    (status, Json(definition)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let template = Template::parse("{{{name}}} has {cAnD13s_3ATeN-yesT3rdAy} candies").unwrap();
+        let reindeer = json!({ "name": "Dasher", "cAnD13s_3ATeN-yesT3rdAy": 2.5 });
+
+        assert_eq!(
+            template.render(reindeer.as_object().unwrap()).as_deref(),
+            Some("{Dasher} has 2.5 candies")
+        );
+        assert_eq!(
+            template.render(json!({ "name": "Dasher" }).as_object().unwrap()),
+            None
+        );
+        assert!(Template::parse("{name").is_err());
+        assert!(Template::parse("name}").is_err());
+        assert!(Template::parse("{}").is_err());
+
+        let templates = Templates::default();
+        assert_eq!(templates.negotiate(None), "en");
+        assert_eq!(templates.negotiate(Some("fr-CA, de;q=0.9")), "fr");
+        assert_eq!(templates.negotiate(Some("es, de;q=0.5, fr;q=0.4")), "de");
+        assert_eq!(templates.negotiate(Some("fr;q=0, *;q=0.1")), "en");
+    }
+}
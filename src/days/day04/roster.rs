@@ -0,0 +1,239 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::db;
+use crate::db::structs::{MyState, StoredReindeer};
+
+/// Stored reindeer as they were given, with their id.
+fn to_json(reindeer: StoredReindeer) -> Value {
+    let mut fields = reindeer.attributes.0;
+    fields.insert("id".to_string(), Value::from(reindeer.id));
+    fields.insert("name".to_string(), Value::from(reindeer.name));
+    Value::Object(fields)
+}
+
+/// Name and the other fields of a reindeer given as a JSON object.
+fn split_name(
+    mut fields: Map<String, Value>,
+) -> Result<(String, Map<String, Value>), (StatusCode, String)> {
+    fields.remove("id");
+    match fields.remove("name") {
+        Some(Value::String(name)) => Ok((name, fields)),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "A reindeer needs a name".to_string(),
+        )),
+    }
+}
+
+pub async fn create_reindeer(
+    State(db): State<MyState>,
+    Json(fields): Json<Map<String, Value>>,
+) -> Response {
+    let (name, attributes) = match split_name(fields) {
+        Ok(reindeer) => reindeer,
+        Err(error) => return error.into_response(),
+    };
+    match db::methods::insert_reindeer(db.clone(), name, attributes).await {
+        Some(id) => {
+            let reindeer = db::methods::get_reindeer(db, id).await.unwrap();
+            (StatusCode::CREATED, Json(to_json(reindeer))).into_response()
+        }
+        None => (
+            StatusCode::CONFLICT,
+            "A reindeer with this name already exists",
+        )
+            .into_response(),
+    }
+}
+
+pub async fn list_reindeer(State(db): State<MyState>) -> (StatusCode, Json<Vec<Value>>) {
+    let reindeer = db::methods::get_all_reindeer(db).await;
+
+    (
+        StatusCode::OK,
+        Json(reindeer.into_iter().map(to_json).collect()),
+    )
+}
+
+pub async fn get_reindeer(Path(id): Path<i32>, State(db): State<MyState>) -> Response {
+    match db::methods::get_reindeer(db, id).await {
+        Some(reindeer) => (StatusCode::OK, Json(to_json(reindeer))).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn update_reindeer(
+    Path(id): Path<i32>,
+    State(db): State<MyState>,
+    Json(fields): Json<Map<String, Value>>,
+) -> Response {
+    let (name, attributes) = match split_name(fields) {
+        Ok(reindeer) => reindeer,
+        Err(error) => return error.into_response(),
+    };
+    match db::methods::update_reindeer(db.clone(), id, name, attributes).await {
+        Ok(true) => {}
+        Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+        // the name is unique in the table, whoever took it first
+        Err(error)
+            if error
+                .as_database_error()
+                .is_some_and(|error| error.is_unique_violation()) =>
+        {
+            return (
+                StatusCode::CONFLICT,
+                "A reindeer with this name already exists",
+            )
+                .into_response();
+        }
+        Err(error) => {
+            return (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+        }
+    }
+    let reindeer = db::methods::get_reindeer(db, id).await.unwrap();
+
+    (StatusCode::OK, Json(to_json(reindeer))).into_response()
+}
+
+pub async fn delete_reindeer(Path(id): Path<i32>, State(db): State<MyState>) -> StatusCode {
+    if db::methods::delete_reindeer(db, id).await {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+/// Stored reindeer taking part in a contest: the given ids (all of them when there
+/// are none) that meet every condition.
+#[derive(Deserialize, Debug)]
+pub struct RosterSelection {
+    ids: Option<Vec<i32>>,
+    #[serde(default)]
+    filter: Vec<Condition>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Condition {
+    field: String,
+    min: Option<f64>,
+    max: Option<f64>,
+    equals: Option<Value>,
+}
+
+impl Condition {
+    fn accepts(&self, reindeer: &Map<String, Value>) -> bool {
+        let value = reindeer.get(&self.field);
+        let number = value.and_then(Value::as_f64);
+        self.min
+            .map_or(true, |min| number.is_some_and(|n| n >= min))
+            && self
+                .max
+                .map_or(true, |max| number.is_some_and(|n| n <= max))
+            && self
+                .equals
+                .as_ref()
+                .map_or(true, |equals| value == Some(equals))
+    }
+}
+
+pub async fn select_roster(
+    db: MyState,
+    selection: &RosterSelection,
+) -> Result<Vec<Map<String, Value>>, (StatusCode, String)> {
+    let stored = db::methods::get_all_reindeer(db).await;
+
+    let chosen: Vec<StoredReindeer> = match &selection.ids {
+        Some(ids) => {
+            if let Some(id) = ids.iter().find(|id| !stored.iter().any(|r| r.id == **id)) {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    format!("No reindeer with the id {id}"),
+                ));
+            }
+            stored.into_iter().filter(|r| ids.contains(&r.id)).collect()
+        }
+        None => stored,
+    };
+
+    Ok(chosen
+        .into_iter()
+        .map(|reindeer| {
+            let mut fields = reindeer.attributes.0;
+            fields.insert("name".to_string(), Value::from(reindeer.name));
+            fields
+        })
+        .filter(|fields| {
+            selection
+                .filter
+                .iter()
+                .all(|condition| condition.accepts(fields))
+        })
+        .collect())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HistoryPlacing {
+    category: String,
+    rank: i32,
+    score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct HistoryContest {
+    contest_id: i32,
+    held_at: DateTime<Utc>,
+    stats: Map<String, Value>,
+    placings: Vec<HistoryPlacing>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct History {
+    name: String,
+    stats: Option<Map<String, Value>>,
+    contests: Vec<HistoryContest>,
+}
+
+pub async fn history(Path(name): Path<String>, State(db): State<MyState>) -> Response {
+    let stats = db::methods::get_reindeer_by_name(db.clone(), &name)
+        .await
+        .map(|reindeer| reindeer.attributes.0);
+    let entries = db::methods::get_contest_entries(db.clone(), &name).await;
+    if stats.is_none() && entries.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let placings = db::methods::get_contest_placings(db, &name).await;
+
+    let contests = entries
+        .into_iter()
+        .map(|entry| HistoryContest {
+            contest_id: entry.contest_id,
+            held_at: entry.held_at,
+            stats: entry.attributes.0,
+            placings: placings
+                .iter()
+                .filter(|placing| placing.contest_id == entry.contest_id)
+                .map(|placing| HistoryPlacing {
+                    category: placing.category.clone(),
+                    rank: placing.rank,
+                    score: placing.score,
+                })
+                .collect(),
+        })
+        .collect();
+
+    (
+        StatusCode::OK,
+        Json(History {
+            name,
+            stats,
+            contests,
+        }),
+    )
+        .into_response()
+}
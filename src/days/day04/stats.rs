@@ -0,0 +1,170 @@
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Numeric fields of `Reindeer`, under their JSON names.
+const FIELDS: [&str; 6] = [
+    "strength",
+    "speed",
+    "height",
+    "antler_width",
+    "snow_magic_power",
+    "cAnD13s_3ATeN-yesT3rdAy",
+];
+
+const DEFAULT_BUCKETS: usize = 5;
+const MAX_BUCKETS: usize = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct StatsOptions {
+    buckets: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Extreme {
+    value: f64,
+    names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Bucket {
+    from: f64,
+    to: f64,
+    count: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct FieldStats {
+    count: usize,
+    sum: f64,
+    mean: Option<f64>,
+    median: Option<f64>,
+    stddev: Option<f64>,
+    min: Option<Extreme>,
+    max: Option<Extreme>,
+    histogram: Vec<Bucket>,
+}
+
+pub async fn stats(
+    Query(options): Query<StatsOptions>,
+    Json(reindeer_list): Json<Vec<Map<String, Value>>>,
+) -> Response {
+    let buckets = options.buckets.unwrap_or(DEFAULT_BUCKETS);
+    if !(1..=MAX_BUCKETS).contains(&buckets) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("buckets must be between 1 and {MAX_BUCKETS}"),
+        )
+            .into_response();
+    }
+
+    let stats: Map<String, Value> = FIELDS
+        .iter()
+        .map(|field| {
+            // reindeer without the field, or with a value that isn't a number, are left out
+            let values: Vec<(f64, Option<&str>)> = reindeer_list
+                .iter()
+                .filter_map(|reindeer| {
+                    let value = reindeer.get(*field)?.as_f64()?;
+                    Some((value, reindeer.get("name").and_then(Value::as_str)))
+                })
+                .collect();
+            let field_stats = field_stats(&values, buckets);
+            (
+                field.to_string(),
+                serde_json::to_value(field_stats).unwrap(),
+            )
+        })
+        .collect();
+
+    (StatusCode::OK, Json(stats)).into_response()
+}
+
+fn field_stats(values: &[(f64, Option<&str>)], buckets: usize) -> FieldStats {
+    let count = values.len();
+    let sum: f64 = values.iter().map(|(value, _)| value).sum();
+    if count == 0 {
+        return FieldStats {
+            count,
+            sum,
+            mean: None,
+            median: None,
+            stddev: None,
+            min: None,
+            max: None,
+            histogram: vec![],
+        };
+    }
+
+    let mean = sum / count as f64;
+    let variance = values
+        .iter()
+        .map(|(value, _)| (value - mean).powi(2))
+        .sum::<f64>()
+        / count as f64;
+
+    let mut sorted: Vec<f64> = values.iter().map(|(value, _)| *value).collect();
+    sorted.sort_by(f64::total_cmp);
+    let median = if count % 2 == 0 {
+        (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+    } else {
+        sorted[count / 2]
+    };
+    let (min, max) = (sorted[0], sorted[count - 1]);
+
+    FieldStats {
+        count,
+        sum,
+        mean: Some(mean),
+        median: Some(median),
+        stddev: Some(variance.sqrt()),
+        min: Some(extreme(values, min)),
+        max: Some(extreme(values, max)),
+        histogram: histogram(&sorted, buckets),
+    }
+}
+
+fn extreme(values: &[(f64, Option<&str>)], value: f64) -> Extreme {
+    Extreme {
+        value,
+        names: values
+            .iter()
+            .filter(|(v, _)| *v == value)
+            .filter_map(|(_, name)| name.map(str::to_string))
+            .collect(),
+    }
+}
+
+/// Buckets of equal width between the smallest and the biggest value, the last one
+/// includes its upper bound.
+fn histogram(sorted: &[f64], buckets: usize) -> Vec<Bucket> {
+    let (min, max) = (sorted[0], sorted[sorted.len() - 1]);
+    if min == max {
+        return vec![Bucket {
+            from: min,
+            to: max,
+            count: sorted.len(),
+        }];
+    }
+
+    let width = (max - min) / buckets as f64;
+    let mut histogram: Vec<Bucket> = (0..buckets)
+        .map(|i| Bucket {
+            from: min + width * i as f64,
+            to: if i == buckets - 1 {
+                max
+            } else {
+                min + width * (i + 1) as f64
+            },
+            count: 0,
+        })
+        .collect();
+    for value in sorted {
+        let i = (((value - min) / width) as usize).min(buckets - 1);
+        histogram[i].count += 1;
+    }
+    histogram
+}
@@ -1,48 +1,77 @@
-use axum::extract::Query;
-use axum::response::IntoResponse;
+use axum::extract::{OriginalUri, Query};
+use axum::http::header::LINK;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::{Json, Router};
-use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use pagination::Pagination;
+
+mod pagination;
+mod pipeline;
 
 pub fn get_day_5_router() -> Router {
     Router::new().route("/", post(slicing_the_loop))
 }
 
-#[derive(Deserialize, Debug)]
-struct Pagination {
-    offset: Option<usize>,
-    limit: Option<usize>,
-    split: Option<usize>,
+#[derive(Serialize, Debug)]
+struct Envelope {
+    items: Value,
+    total: usize,
+    next: Option<String>,
+    prev: Option<String>,
 }
 
 async fn slicing_the_loop(
-    pagination: Query<Pagination>,
-    names: Json<Vec<String>>,
-) -> impl IntoResponse {
-    if names.is_empty() {
-        return Json(names.0).into_response();
-    }
-    let limit = pagination.limit.unwrap_or(names.len());
+    OriginalUri(uri): OriginalUri,
+    Query(pagination): Query<Pagination>,
+    Json(items): Json<Vec<Value>>,
+) -> Response {
+    let items = match pipeline::apply(&pagination, items) {
+        Ok(items) => items,
+        Err(error) => return error.into_response(),
+    };
+    let window = match pagination.window(items.len()) {
+        Ok(window) => window,
+        Err(error) => return error.into_response(),
+    };
+    let total = items.len();
+    let page = &items[window.start..window.end];
 
-    if limit == 0 {
-        let empty: Vec<String> = vec![];
-        return Json(empty).into_response();
+    let page = match pagination.split {
+        Some(split) if split > 0 => serde_json::to_value(page.chunks(split).collect::<Vec<_>>()),
+        _ => serde_json::to_value(page),
     }
+    .unwrap();
+    let next = window.next(&uri);
+    let prev = window.prev(&uri);
 
-    let start = pagination.offset.unwrap_or(0);
-
-    let end = std::cmp::min(start + limit, names.len());
+    let links = [(&next, "next"), (&prev, "prev")]
+        .into_iter()
+        .filter_map(|(link, rel)| Some(format!("<{}>; rel=\"{rel}\"", link.as_ref()?)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut headers = HeaderMap::new();
+    headers.insert("x-total-count", HeaderValue::from(total));
+    if !links.is_empty() {
+        headers.insert(LINK, HeaderValue::from_str(&links).unwrap());
+    }
 
-    if pagination.split.unwrap_or(0) == 0 {
-        Json(&names[start..end]).into_response()
-    } else {
-        Json(
-            &names[start..end]
-                .chunks(pagination.split.unwrap())
-                .collect::<Vec<_>>(),
-        )
+    let body = if pagination.envelope {
+        Json(Envelope {
+            items: page,
+            total,
+            next,
+            prev,
+        })
         .into_response()
-    }
+    } else {
+        Json(page).into_response()
+    };
+
+    (StatusCode::OK, headers, body).into_response()
 }
 
 #[cfg(test)]
@@ -130,4 +159,261 @@ mod tests {
             ["Mason", "Olivia"]
         ]));
     }
+
+    #[tokio::test]
+    async fn out_of_range() {
+        let app = get_day_5_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("offset", 20)
+            .add_query_param("limit", 5)
+            .json(&json!(["Ava", "Caleb", "Mia"]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([]));
+        assert_eq!(response.header("x-total-count"), "3");
+        assert_eq!(response.header(LINK), "</?offset=0&limit=5>; rel=\"prev\"");
+    }
+
+    #[tokio::test]
+    async fn pages() {
+        let app = get_day_5_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("split", 2)
+            .add_query_param("page", 2)
+            .add_query_param("per_page", 5)
+            .json(&json!([
+                "Ava", "Caleb", "Mia", "Owen", "Lily", "Ethan", "Zoe", "Nolan", "Harper", "Lucas",
+                "Stella", "Mason", "Olivia"
+            ]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([["Ethan", "Zoe"], ["Nolan", "Harper"], ["Lucas"]]));
+        assert_eq!(response.header("x-total-count"), "13");
+        assert_eq!(
+            response.header(LINK),
+            "</?split=2&page=3&per_page=5>; rel=\"next\", </?split=2&page=1&per_page=5>; rel=\"prev\""
+        );
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("page", 2)
+            .add_query_param("offset", 5)
+            .json(&json!(["Ava"]))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn cursors() {
+        let app = get_day_5_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let names = json!(["Ava", "Caleb", "Mia", "Owen", "Lily"]);
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("cursor", "")
+            .add_query_param("limit", 2)
+            .add_query_param("envelope", true)
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let first = response.json::<Value>();
+        assert_eq!(first["items"], json!(["Ava", "Caleb"]));
+        assert_eq!(first["total"], 5);
+        assert_eq!(first["prev"], Value::Null);
+
+        let next = first["next"].as_str().unwrap();
+        assert!(next.starts_with("/?envelope=true&cursor="));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("envelope", true)
+            .add_query_param("cursor", next.rsplit_once('=').unwrap().1)
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let second = response.json::<Value>();
+        assert_eq!(second["items"], json!(["Mia", "Owen"]));
+        assert!(second["prev"].as_str().unwrap().contains("cursor="));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("cursor", "not a cursor")
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn pipeline() {
+        let app = get_day_5_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let names = json!([
+            "Ava", "Caleb", "Mia", "Owen", "Lily", "Ethan", "Zoe", "Nolan", "Harper", "Lucas",
+            "Stella", "Mason", "Olivia", "Lily", "Mia"
+        ]);
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("filter", "regex:^[LM]")
+            .add_query_param("unique", true)
+            .add_query_param("sort", "name")
+            .add_query_param("order", "desc")
+            .add_query_param("limit", 3)
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!(["Mia", "Mason", "Lucas"]));
+        assert_eq!(response.header("x-total-count"), "4");
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("filter", "length:..3")
+            .add_query_param("sort", "length")
+            .add_query_param("group_by", "first_letter")
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+            { "key": "A", "items": ["Ava"] },
+            { "key": "M", "items": ["Mia", "Mia"] },
+            { "key": "Z", "items": ["Zoe"] }
+        ]));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("group_by", "first_letter")
+            .add_query_param("split", 2)
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("order", "desc")
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("filter", "suffix:a")
+            .json(&names)
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn json_values() {
+        let app = get_day_5_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let orders = json!([
+            { "id": 1, "gift": { "name": "Toy Train", "quantity": 5 } },
+            { "id": 2, "gift": { "name": "Doll", "quantity": 8 } },
+            { "id": 3 },
+            { "id": 4, "gift": { "name": "Teddy Bear", "quantity": 12 } },
+            { "id": 5, "gift": { "name": "Toy Train", "quantity": 3 } }
+        ]);
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("key", "/gift/quantity")
+            .add_query_param("sort", "value")
+            .add_query_param("order", "desc")
+            .add_query_param("limit", 2)
+            .json(&orders)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+            { "id": 4, "gift": { "name": "Teddy Bear", "quantity": 12 } },
+            { "id": 2, "gift": { "name": "Doll", "quantity": 8 } }
+        ]));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("key", "/gift/name")
+            .add_query_param("filter", "prefix:T")
+            .add_query_param("unique", true)
+            .json(&orders)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+            { "id": 1, "gift": { "name": "Toy Train", "quantity": 5 } },
+            { "id": 4, "gift": { "name": "Teddy Bear", "quantity": 12 } }
+        ]));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("sort", "value")
+            .add_query_param("split", 2)
+            .json(&json!([3, "b", null, [1], 1.5, "a", true]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([[null, true], [1.5, 3], ["a", "b"], [[1]]]));
+
+        // Send the request.
+        let response = server
+            .post("/")
+            .add_query_param("key", "gift")
+            .json(&orders)
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,173 @@
+use axum::http::{StatusCode, Uri};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+
+use super::pipeline::{GroupBy, SortKey, SortOrder};
+
+const DEFAULT_PER_PAGE: usize = 10;
+
+/// Query parameters choosing the page, dropped from the query when building links.
+const PAGINATION_KEYS: [&str; 5] = ["offset", "limit", "page", "per_page", "cursor"];
+
+#[derive(Deserialize, Debug)]
+pub struct Pagination {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    pub split: Option<usize>,
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+    /// Token of a previous response, or empty to start paging with cursors.
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub envelope: bool,
+    pub sort: Option<SortKey>,
+    pub order: Option<SortOrder>,
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub unique: bool,
+    pub group_by: Option<GroupBy>,
+    /// JSON pointer to the value the operators look at in each item.
+    pub key: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Offset,
+    Page,
+    Cursor,
+}
+
+/// The part of the list to return, clamped to its length.
+#[derive(Debug)]
+pub struct Window {
+    mode: Mode,
+    requested: usize,
+    pub start: usize,
+    pub end: usize,
+    limit: Option<usize>,
+    total: usize,
+}
+
+fn encode_cursor(offset: usize, limit: Option<usize>) -> String {
+    let limit = limit.map(|limit| limit.to_string()).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(format!("{offset}:{limit}"))
+}
+
+fn decode_cursor(cursor: &str) -> Option<(usize, Option<usize>)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (offset, limit) = decoded.split_once(':')?;
+    let limit = match limit {
+        "" => None,
+        limit => Some(limit.parse().ok()?),
+    };
+    Some((offset.parse().ok()?, limit))
+}
+
+impl Pagination {
+    pub fn window(&self, total: usize) -> Result<Window, (StatusCode, String)> {
+        let bad_request = |message: &str| Err((StatusCode::BAD_REQUEST, message.to_string()));
+        let by_offset = self.offset.is_some() || self.limit.is_some();
+        let by_page = self.page.is_some() || self.per_page.is_some();
+
+        let (mode, start, limit) = if let Some(cursor) = &self.cursor {
+            if self.offset.is_some() || by_page {
+                return bad_request("A cursor can't be combined with offset or page");
+            }
+            let (offset, limit) = match cursor.as_str() {
+                "" => (0, None),
+                cursor => match decode_cursor(cursor) {
+                    Some(position) => position,
+                    None => return bad_request("Invalid cursor"),
+                },
+            };
+            (Mode::Cursor, offset, self.limit.or(limit))
+        } else if by_page {
+            if by_offset {
+                return bad_request("Pages can't be combined with offset or limit");
+            }
+            let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+            let page = self.page.unwrap_or(1);
+            if per_page == 0 || page == 0 {
+                return bad_request("Pages start at 1 and hold at least one item");
+            }
+            match (page - 1).checked_mul(per_page) {
+                Some(start) => (Mode::Page, start, Some(per_page)),
+                None => return bad_request("Page out of range"),
+            }
+        } else {
+            (Mode::Offset, self.offset.unwrap_or(0), self.limit)
+        };
+
+        // out of range offsets give an empty page
+        let requested = start;
+        let start = start.min(total);
+        let end = match limit {
+            Some(limit) => start.saturating_add(limit).min(total),
+            None => total,
+        };
+
+        Ok(Window {
+            mode,
+            requested,
+            start,
+            end,
+            limit,
+            total,
+        })
+    }
+}
+
+impl Window {
+    fn link(&self, uri: &Uri, start: usize, limit: Option<usize>) -> String {
+        let mut query: Vec<String> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap();
+                !pair.is_empty() && !PAGINATION_KEYS.contains(&key)
+            })
+            .map(str::to_string)
+            .collect();
+
+        match self.mode {
+            Mode::Offset => {
+                query.push(format!("offset={start}"));
+                if let Some(limit) = limit {
+                    query.push(format!("limit={limit}"));
+                }
+            }
+            Mode::Page => {
+                let per_page = limit.unwrap();
+                query.push(format!("page={}", start / per_page + 1));
+                query.push(format!("per_page={per_page}"));
+            }
+            Mode::Cursor => query.push(format!("cursor={}", encode_cursor(start, limit))),
+        }
+
+        format!("{}?{}", uri.path(), query.join("&"))
+    }
+
+    pub fn next(&self, uri: &Uri) -> Option<String> {
+        let limit = self.limit.filter(|&limit| limit > 0)?;
+        (self.end < self.total).then(|| self.link(uri, self.end, Some(limit)))
+    }
+
+    pub fn prev(&self, uri: &Uri) -> Option<String> {
+        if self.start == 0 {
+            return None;
+        }
+        match self.limit {
+            Some(0) => None,
+            // pages stay aligned, even from past the end of the list
+            Some(limit) if self.mode == Mode::Page => {
+                let last_page = (self.total - 1) / limit;
+                let page = (self.requested / limit - 1).min(last_page);
+                Some(self.link(uri, page * limit, Some(limit)))
+            }
+            Some(limit) => Some(self.link(uri, self.start.saturating_sub(limit), Some(limit))),
+            None => Some(self.link(uri, 0, Some(self.start))),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+use axum::http::StatusCode;
+use ordered_float::OrderedFloat;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::pagination::Pagination;
+
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// `name` from when only names were paged.
+    #[serde(alias = "name")]
+    Value,
+    Length,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    FirstLetter,
+}
+
+/// `prefix:<text>`, `regex:<pattern>` or `length:<n>`, `length:<min>..<max>` with open bounds.
+enum Filter {
+    Prefix(String),
+    Regex(Regex),
+    Length(RangeInclusive<usize>),
+}
+
+fn parse_length(bounds: &str) -> Option<RangeInclusive<usize>> {
+    let Some((min, max)) = bounds.split_once("..") else {
+        let length = bounds.parse().ok()?;
+        return Some(length..=length);
+    };
+    let min = match min {
+        "" => 0,
+        min => min.parse().ok()?,
+    };
+    let max = match max {
+        "" => usize::MAX,
+        max => max.parse().ok()?,
+    };
+    Some(min..=max)
+}
+
+impl Filter {
+    fn parse(filter: &str) -> Result<Filter, String> {
+        match filter.split_once(':') {
+            Some(("prefix", prefix)) => Ok(Filter::Prefix(prefix.to_string())),
+            Some(("regex", pattern)) => RegexBuilder::new(pattern)
+                .size_limit(MAX_REGEX_SIZE)
+                .build()
+                .map(Filter::Regex)
+                .map_err(|error| format!("Invalid regex: {error}")),
+            Some(("length", bounds)) => parse_length(bounds)
+                .map(Filter::Length)
+                .ok_or(format!("Invalid length bounds '{bounds}'")),
+            _ => Err(format!(
+                "Unknown filter '{filter}', expected prefix:, regex: or length:"
+            )),
+        }
+    }
+
+    fn matches(&self, subject: Option<&Value>) -> bool {
+        match self {
+            Filter::Prefix(prefix) => text(subject).is_some_and(|text| text.starts_with(prefix)),
+            Filter::Regex(regex) => text(subject).is_some_and(|text| regex.is_match(&text)),
+            Filter::Length(range) => length(subject).is_some_and(|length| range.contains(&length)),
+        }
+    }
+}
+
+/// Strings and numbers, as they read.
+fn text(subject: Option<&Value>) -> Option<Cow<'_, str>> {
+    match subject? {
+        Value::String(text) => Some(Cow::Borrowed(text)),
+        Value::Number(number) => Some(Cow::Owned(number.to_string())),
+        _ => None,
+    }
+}
+
+fn length(subject: Option<&Value>) -> Option<usize> {
+    match subject? {
+        Value::String(text) => Some(text.chars().count()),
+        Value::Array(values) => Some(values.len()),
+        Value::Object(fields) => Some(fields.len()),
+        _ => None,
+    }
+}
+
+/// Values of a kind compare naturally, different kinds in the order of the variants.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Sortable<'a> {
+    Null,
+    Bool(bool),
+    Number(OrderedFloat<f64>),
+    String(&'a str),
+    // arrays and objects, by their JSON text
+    Other(String),
+}
+
+impl<'a> From<&'a Value> for Sortable<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Null => Sortable::Null,
+            Value::Bool(value) => Sortable::Bool(*value),
+            Value::Number(value) => Sortable::Number(OrderedFloat(value.as_f64().unwrap())),
+            Value::String(value) => Sortable::String(value),
+            value => Sortable::Other(value.to_string()),
+        }
+    }
+}
+
+impl SortKey {
+    fn of(self, subject: Option<&Value>) -> Option<Sortable<'_>> {
+        match self {
+            SortKey::Value => subject.map(Sortable::from),
+            SortKey::Length => Some(Sortable::Number(OrderedFloat(length(subject)? as f64))),
+        }
+    }
+}
+
+fn first_letter(subject: Option<&Value>) -> String {
+    text(subject)
+        .and_then(|text| text.chars().next())
+        .map(|c| c.to_uppercase().collect())
+        .unwrap_or_default()
+}
+
+/// Filters, dedups, sorts then groups the items, giving the ones to page through.
+/// With a `key`, the operators look at the value it points to in each item.
+pub fn apply(
+    pagination: &Pagination,
+    items: Vec<Value>,
+) -> Result<Vec<Value>, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+    if pagination.order.is_some() && pagination.sort.is_none() {
+        return Err(bad_request("An order needs a sort".to_string()));
+    }
+    if pagination.group_by.is_some() && pagination.split.is_some_and(|split| split > 0) {
+        return Err(bad_request("Groups can't be split".to_string()));
+    }
+    let key = pagination.key.as_deref();
+    if key.is_some_and(|key| !key.is_empty() && !key.starts_with('/')) {
+        return Err(bad_request(
+            "The key must be a JSON pointer, like /name".to_string(),
+        ));
+    }
+    let subject = |item: &Value| -> Option<Value> {
+        match key {
+            Some(key) => item.pointer(key).cloned(),
+            None => Some(item.clone()),
+        }
+    };
+    let filter = pagination
+        .filter
+        .as_deref()
+        .map(Filter::parse)
+        .transpose()
+        .map_err(bad_request)?;
+
+    let mut items: Vec<(Option<Value>, Value)> = items
+        .into_iter()
+        .map(|item| (subject(&item), item))
+        .filter(|(subject, _)| {
+            filter
+                .as_ref()
+                .map_or(true, |filter| filter.matches(subject.as_ref()))
+        })
+        .collect();
+
+    if pagination.unique {
+        // items without the key count as null
+        let mut seen = HashSet::new();
+        items.retain(|(subject, _)| {
+            seen.insert(subject.as_ref().unwrap_or(&Value::Null).to_string())
+        });
+    }
+
+    if let Some(sort) = pagination.sort {
+        let order = pagination.order.unwrap_or_default();
+        let compare = |a: &(Option<Value>, Value), b: &(Option<Value>, Value)| -> Ordering {
+            // items without the key, or without a length, go last
+            match (sort.of(a.0.as_ref()), sort.of(b.0.as_ref())) {
+                (Some(a), Some(b)) => match order {
+                    SortOrder::Asc => a.cmp(&b),
+                    SortOrder::Desc => b.cmp(&a),
+                },
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        };
+        // stable, equal items keep their input order either way
+        items.sort_by(compare);
+    }
+
+    let items = match pagination.group_by {
+        None => items.into_iter().map(|(_, item)| item).collect(),
+        // groups come in the order of their first item
+        Some(GroupBy::FirstLetter) => {
+            let mut groups: Vec<(String, Vec<Value>)> = Vec::new();
+            let mut positions: HashMap<String, usize> = HashMap::new();
+            for (subject, item) in items {
+                let letter = first_letter(subject.as_ref());
+                match positions.get(&letter) {
+                    Some(&position) => groups[position].1.push(item),
+                    None => {
+                        positions.insert(letter.clone(), groups.len());
+                        groups.push((letter, vec![item]));
+                    }
+                }
+            }
+            groups
+                .into_iter()
+                .map(|(key, items)| json!({ "key": key, "items": items }))
+                .collect()
+        }
+    };
+
+    Ok(items)
+}
@@ -1,10 +1,24 @@
-use axum::response::IntoResponse;
+use axum::body::Body;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
 use axum::{Json, Router};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use tower_http::decompression::RequestDecompressionLayer;
+
+use counter::{Count, Counter, PatternSpec};
+
+mod counter;
 
 pub fn get_day_6_router() -> Router {
-    Router::new().route("/", post(count_elves))
+    Router::new()
+        .route("/", post(count_elves))
+        .route("/count", post(count))
+        .route(
+            "/stream",
+            post(stream_elves).layer(RequestDecompressionLayer::new()),
+        )
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -16,31 +30,85 @@ struct Res {
     shelf_with_no_elf_on_it: usize,
 }
 
-async fn count_elves(body: String) -> impl IntoResponse {
-    const WINDOW_LENGTH: usize = "elf on a shelf".len();
-
-    let elf = body.matches("elf").count();
-    let shelf = body.matches("shelf").count();
-    let elf_on_a_shelf = body
-        .chars()
-        .collect::<Vec<_>>()
-        .windows(WINDOW_LENGTH)
-        .filter(|window| window.iter().collect::<String>() == "elf on a shelf")
-        .count();
-    let shelf_with_no_elf_on_it = shelf - elf_on_a_shelf;
-
-    Json(Res {
+fn elf_counter() -> Counter {
+    Counter::new(
+        ["elf", "shelf", "elf on a shelf"]
+            .map(|pattern| PatternSpec::Literal(pattern.to_string()))
+            .into(),
+        true,
+    )
+    .unwrap()
+}
+
+fn elf_result(counts: Vec<Count>) -> Res {
+    let [elf, shelf, elf_on_a_shelf] = counts
+        .try_into()
+        .map(|counts: [Count; 3]| counts.map(|count| count.count))
+        .unwrap();
+
+    Res {
         elf,
         elf_on_a_shelf,
-        shelf_with_no_elf_on_it,
-    })
-    .into_response()
+        shelf_with_no_elf_on_it: shelf - elf_on_a_shelf,
+    }
+}
+
+async fn count_elves(body: String) -> impl IntoResponse {
+    Json(elf_result(elf_counter().count(&body, false))).into_response()
+}
+
+/// Same as `count_elves`, reading the body as it comes, possibly compressed.
+async fn stream_elves(body: Body) -> Response {
+    let counter = elf_counter();
+    let mut stream = counter.stream().unwrap();
+
+    let mut chunks = body.into_data_stream();
+    while let Some(chunk) = chunks.next().await {
+        match chunk {
+            Ok(chunk) => stream.feed(&chunk),
+            Err(error) => return (StatusCode::BAD_REQUEST, error.to_string()).into_response(),
+        }
+    }
+
+    Json(elf_result(stream.finish())).into_response()
+}
+
+#[derive(Deserialize, Debug)]
+struct CountRequest {
+    text: String,
+    patterns: Vec<PatternSpec>,
+    #[serde(default)]
+    overlapping: bool,
+    #[serde(default)]
+    offsets: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct CountResult {
+    counts: Vec<Count>,
+}
+
+async fn count(Json(request): Json<CountRequest>) -> Response {
+    let counter = Counter::new(request.patterns, request.overlapping)
+        .and_then(|counter| counter.check(&request.text).map(|_| counter));
+    match counter {
+        Ok(counter) => Json(CountResult {
+            counts: counter.count(&request.text, request.offsets),
+        })
+        .into_response(),
+        Err(error) => (StatusCode::BAD_REQUEST, error).into_response(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
+    use axum::http::header::CONTENT_ENCODING;
     use axum::http::StatusCode;
     use axum_test::TestServer;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
     use serde_json::json;
 
     use super::*;
@@ -91,4 +159,154 @@ mod tests {
             "shelf with no elf on it": 1
         }));
     }
+
+    #[tokio::test]
+    async fn count() {
+        let app = get_day_6_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/count")
+            .json(&json!({
+                "text": "An Elf on a shelf, an elf on a shelf on a shelf. Élan, self-elf.",
+                "patterns": [
+                    "elf on a shelf",
+                    { "pattern": "ELF", "case_insensitive": true, "whole_word": true },
+                    { "pattern": "élan", "case_insensitive": true },
+                    { "pattern": "s[a-z]*f", "kind": "regex" }
+                ],
+                "overlapping": true,
+                "offsets": true
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+            "counts": [
+                { "pattern": "elf on a shelf", "count": 2, "offsets": [22, 33] },
+                { "pattern": "ELF", "count": 3, "offsets": [3, 22, 61] },
+                { "pattern": "élan", "count": 1, "offsets": [49] },
+                { "pattern": "s[a-z]*f", "count": 4, "offsets": [12, 31, 42, 56] }
+            ]
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/count")
+            .json(&json!({
+                "text": "elf on a shelf on a shelf",
+                "patterns": ["elf on a shelf", { "pattern": "s[a-z]*f", "kind": "regex" }]
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+            "counts": [
+                { "pattern": "elf on a shelf", "count": 1 },
+                { "pattern": "s[a-z]*f", "count": 2 }
+            ]
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/count")
+            .json(&json!({ "text": "elf", "patterns": [{ "pattern": "e*", "kind": "regex" }] }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // A pattern only matching empty text next to words.
+        for overlapping in [false, true] {
+            // Send the request.
+            let response = server
+                .post("/count")
+                .json(&json!({
+                    "text": "an elf and élves",
+                    "patterns": [{ "pattern": "\\b", "kind": "regex" }],
+                    "overlapping": overlapping,
+                    "offsets": true
+                }))
+                .await;
+
+            response.assert_status(StatusCode::OK);
+
+            response.assert_json(&json!({
+                "counts": [
+                    { "pattern": "\\b", "count": 8, "offsets": [0, 2, 3, 6, 7, 10, 11, 17] }
+                ]
+            }));
+        }
+
+        // Send the request.
+        let response = server
+            .post("/count")
+            .json(&json!({
+                "text": "elf ".repeat(20_000),
+                "patterns": [{ "pattern": "e[a-z]*", "kind": "regex" }],
+                "overlapping": true
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let text = "there is an elf on a shelf on a shelf on an elf.
+                there is also another shelf in Belfast.";
+        let counter = elf_counter();
+
+        // every boundary falls inside some match when fed byte by byte
+        let mut stream = counter.stream().unwrap();
+        text.as_bytes()
+            .chunks(1)
+            .for_each(|chunk| stream.feed(chunk));
+
+        assert_eq!(stream.finish(), counter.count(text, false));
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let app = get_day_6_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(
+                b"there is an elf on a shelf on an elf.
+                there is also another shelf in Belfast.",
+            )
+            .unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/stream")
+            .add_header(CONTENT_ENCODING, "gzip".parse().unwrap())
+            .bytes(encoder.finish().unwrap().into())
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+            "elf": 5,
+            "elf on a shelf": 1,
+            "shelf with no elf on it": 1
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/stream")
+            .add_header(CONTENT_ENCODING, "compress".parse().unwrap())
+            .bytes("elf".into())
+            .await;
+
+        response.assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
 }
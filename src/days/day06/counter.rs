@@ -0,0 +1,310 @@
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+const MAX_PATTERNS: usize = 1000;
+const MAX_REGEX_SIZE: usize = 1 << 20;
+/// Overlapping regexes are tried again at every character of the text, so only over this many bytes.
+const MAX_OVERLAPPING_TEXT: usize = 1 << 16;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Literal,
+    Regex,
+}
+
+/// A pattern, either as a bare literal or with its options.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PatternSpec {
+    Literal(String),
+    Detailed {
+        pattern: String,
+        #[serde(default)]
+        kind: Kind,
+        #[serde(default)]
+        case_insensitive: bool,
+        #[serde(default)]
+        whole_word: bool,
+    },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Count {
+    pub pattern: String,
+    pub count: usize,
+    /// Byte offsets of the start of each match.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offsets: Option<Vec<usize>>,
+}
+
+struct Literal {
+    needle: String,
+    case_insensitive: bool,
+    whole_word: bool,
+}
+
+enum Matcher {
+    /// Counted by the automaton.
+    Literal,
+    Regex(Regex),
+}
+
+/// Counts every pattern over a text, literals all at once through one automaton.
+pub struct Counter {
+    sources: Vec<String>,
+    matchers: Vec<Matcher>,
+    literals: Vec<(usize, Literal)>,
+    automaton: Option<AhoCorasick>,
+    overlapping: bool,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    !text[..start].chars().next_back().is_some_and(is_word_char)
+        && !text[end..].chars().next().is_some_and(is_word_char)
+}
+
+impl Counter {
+    pub fn new(patterns: Vec<PatternSpec>, overlapping: bool) -> Result<Counter, String> {
+        if patterns.len() > MAX_PATTERNS {
+            return Err(format!(
+                "At most {MAX_PATTERNS} patterns are counted at once"
+            ));
+        }
+
+        let mut sources = Vec::with_capacity(patterns.len());
+        let mut matchers = Vec::with_capacity(patterns.len());
+        let mut literals = Vec::new();
+        for spec in patterns {
+            let (pattern, kind, case_insensitive, whole_word) = match spec {
+                PatternSpec::Literal(pattern) => (pattern, Kind::Literal, false, false),
+                PatternSpec::Detailed {
+                    pattern,
+                    kind,
+                    case_insensitive,
+                    whole_word,
+                } => (pattern, kind, case_insensitive, whole_word),
+            };
+            if pattern.is_empty() {
+                return Err("Patterns can't be empty".to_string());
+            }
+
+            // the automaton only folds ASCII, other literals are left to the regex engine
+            let matcher = if kind == Kind::Literal && (!case_insensitive || pattern.is_ascii()) {
+                literals.push((
+                    matchers.len(),
+                    Literal {
+                        needle: pattern.clone(),
+                        case_insensitive,
+                        whole_word,
+                    },
+                ));
+                Matcher::Literal
+            } else {
+                let expression = match kind {
+                    Kind::Literal => regex::escape(&pattern),
+                    Kind::Regex => pattern.clone(),
+                };
+                let expression = if whole_word {
+                    format!(r"\b(?:{expression})\b")
+                } else {
+                    expression
+                };
+                let regex = RegexBuilder::new(&expression)
+                    .case_insensitive(case_insensitive)
+                    .size_limit(MAX_REGEX_SIZE)
+                    .build()
+                    .map_err(|error| format!("Invalid regex '{pattern}': {error}"))?;
+                if regex.is_match("") {
+                    return Err(format!("'{pattern}' matches empty text"));
+                }
+                Matcher::Regex(regex)
+            };
+            sources.push(pattern);
+            matchers.push(matcher);
+        }
+
+        let automaton = if literals.is_empty() {
+            None
+        } else {
+            let automaton = AhoCorasick::builder()
+                .ascii_case_insensitive(literals.iter().any(|(_, l)| l.case_insensitive))
+                .build(literals.iter().map(|(_, literal)| &literal.needle))
+                .map_err(|error| error.to_string())?;
+            Some(automaton)
+        };
+
+        Ok(Counter {
+            sources,
+            matchers,
+            literals,
+            automaton,
+            overlapping,
+        })
+    }
+
+    /// Refuses texts too long to count the patterns over in reasonable time.
+    pub fn check(&self, text: &str) -> Result<(), String> {
+        let regexes = self
+            .matchers
+            .iter()
+            .any(|matcher| matches!(matcher, Matcher::Regex(_)));
+        if self.overlapping && regexes && text.len() > MAX_OVERLAPPING_TEXT {
+            return Err(format!(
+                "Overlapping regexes are counted over at most {MAX_OVERLAPPING_TEXT} bytes"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn count(&self, text: &str, with_offsets: bool) -> Vec<Count> {
+        let mut counts = vec![0; self.matchers.len()];
+        let mut offsets: Vec<Vec<usize>> = vec![Vec::new(); self.matchers.len()];
+        let mut record = |index: usize, start: usize| {
+            counts[index] += 1;
+            if with_offsets {
+                offsets[index].push(start);
+            }
+        };
+
+        if let Some(automaton) = &self.automaton {
+            // one pass for all literals, each keeping its own non-overlapping matches
+            let mut next_start = vec![0; self.literals.len()];
+            for found in automaton.find_overlapping_iter(text) {
+                let id = found.pattern().as_usize();
+                let (index, literal) = &self.literals[id];
+                let (start, end) = (found.start(), found.end());
+                if (!self.overlapping && start < next_start[id])
+                    || (!literal.case_insensitive && text[start..end] != literal.needle)
+                    || (literal.whole_word && !is_whole_word(text, start, end))
+                {
+                    continue;
+                }
+                next_start[id] = end;
+                record(*index, start);
+            }
+        }
+
+        for (index, matcher) in self.matchers.iter().enumerate() {
+            let Matcher::Regex(regex) = matcher else {
+                continue;
+            };
+            let mut position = 0;
+            while position <= text.len() {
+                let Some(found) = regex.find_at(text, position) else {
+                    break;
+                };
+                record(index, found.start());
+                // empty matches, like `\b`, still have to move on past a character
+                position = if self.overlapping || found.is_empty() {
+                    found.start()
+                        + text[found.start()..]
+                            .chars()
+                            .next()
+                            .map_or(1, char::len_utf8)
+                } else {
+                    found.end()
+                };
+            }
+        }
+
+        self.sources
+            .iter()
+            .zip(counts)
+            .zip(offsets)
+            .map(|((pattern, count), offsets)| Count {
+                pattern: pattern.clone(),
+                count,
+                offsets: with_offsets.then_some(offsets),
+            })
+            .collect()
+    }
+}
+
+/// Counts over a text fed in chunks, see [`Counter::stream`].
+pub struct CountStream<'a> {
+    counter: &'a Counter,
+    automaton: &'a AhoCorasick,
+    /// The end of what was fed, long enough to hold the start of a match spanning chunks.
+    tail: Vec<u8>,
+    /// Offset of the tail in the whole text.
+    offset: usize,
+    counts: Vec<usize>,
+    next_start: Vec<usize>,
+}
+
+impl Counter {
+    /// `None` unless all patterns are literals not restricted to whole words,
+    /// the only ones a bounded window of the text is enough for.
+    pub fn stream(&self) -> Option<CountStream<'_>> {
+        if self
+            .matchers
+            .iter()
+            .any(|matcher| matches!(matcher, Matcher::Regex(_)))
+            || self.literals.iter().any(|(_, literal)| literal.whole_word)
+        {
+            return None;
+        }
+
+        Some(CountStream {
+            counter: self,
+            automaton: self.automaton.as_ref()?,
+            tail: Vec::new(),
+            offset: 0,
+            counts: vec![0; self.matchers.len()],
+            next_start: vec![0; self.literals.len()],
+        })
+    }
+}
+
+impl CountStream<'_> {
+    pub fn feed(&mut self, chunk: &[u8]) {
+        let searched = self.tail.len();
+        self.tail.extend_from_slice(chunk);
+
+        for found in self.automaton.find_overlapping_iter(&self.tail) {
+            // matches within the tail were counted with the previous chunk
+            if found.end() <= searched {
+                continue;
+            }
+            let id = found.pattern().as_usize();
+            let (index, literal) = &self.counter.literals[id];
+            let start = self.offset + found.start();
+            if (!self.counter.overlapping && start < self.next_start[id])
+                || (!literal.case_insensitive
+                    && self.tail[found.range()] != *literal.needle.as_bytes())
+            {
+                continue;
+            }
+            self.next_start[id] = self.offset + found.end();
+            self.counts[*index] += 1;
+        }
+
+        let keep = self
+            .tail
+            .len()
+            .min(self.automaton.max_pattern_len().saturating_sub(1));
+        let consumed = self.tail.len() - keep;
+        self.tail.drain(..consumed);
+        self.offset += consumed;
+    }
+
+    pub fn finish(self) -> Vec<Count> {
+        self.counter
+            .sources
+            .iter()
+            .zip(self.counts)
+            .map(|(pattern, count)| Count {
+                pattern: pattern.clone(),
+                count,
+                offsets: None,
+            })
+            .collect()
+    }
+}
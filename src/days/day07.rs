@@ -1,32 +1,37 @@
 use std::collections::HashMap;
 
-use axum::routing::get;
+use axum::http::StatusCode;
+use axum::routing::{get, post};
 use axum::{Json, Router};
-use axum_extra::extract::CookieJar;
-use base64::engine::general_purpose;
-use base64::Engine;
 use serde::{Deserialize, Serialize};
-use serde_json::{from_slice, Value};
+use serde_json::Value;
 
-pub fn get_day_7_router() -> Router {
+pub use cookies::CookieConfig;
+use input::RecipeInput;
+use units::Quantity;
+
+mod cookies;
+mod input;
+mod planner;
+mod units;
+
+pub fn get_day_7_router(cookies: CookieConfig) -> Router {
     Router::new()
-        .route("/decode", get(decode_cookie))
-        .route("/bake", get(bake))
+        .route("/decode", get(decode_cookie).post(decode_cookie))
+        .route("/bake", get(bake).post(bake))
+        .route("/recipe", post(cookies::issue_recipe))
+        .route("/scale", post(scale))
+        .route("/plan", post(plan))
+        .with_state(cookies)
 }
 
 type Recipe = HashMap<String, Value>;
 
-async fn decode_cookie(jar: CookieJar) -> Json<Recipe> {
-    let encoded_recipe = jar.get("recipe").unwrap().value();
-    let decoded_bytes = general_purpose::STANDARD
-        .decode(encoded_recipe)
-        .expect("Failed to decode Base64");
-    let decoded_json: Recipe = from_slice(&decoded_bytes).expect("Failed to parse JSON");
-
-    Json(decoded_json)
+async fn decode_cookie(RecipeInput(recipe): RecipeInput<Recipe>) -> Json<Recipe> {
+    Json(recipe)
 }
 
-type Ingredients = HashMap<String, usize>;
+type Ingredients = HashMap<String, Quantity>;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct BakeResult {
@@ -40,26 +45,68 @@ struct BakeInstructions {
     pantry: Ingredients,
 }
 
-async fn bake(jar: CookieJar) -> Json<BakeResult> {
-    let encoded_recipe = jar.get("recipe").unwrap().value();
-    let decoded_bytes = general_purpose::STANDARD
-        .decode(encoded_recipe)
-        .expect("Failed to decode Base64");
-    let bake_instructions: BakeInstructions =
-        from_slice::<BakeInstructions>(&decoded_bytes).expect("Failed to parse JSON");
+async fn bake(
+    RecipeInput(bake_instructions): RecipeInput<BakeInstructions>,
+) -> Result<Json<BakeResult>, (StatusCode, String)> {
+    bake_batches(bake_instructions)
+        .map(Json)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))
+}
+
+fn validate(ingredients: &Ingredients) -> Result<(), String> {
+    ingredients
+        .iter()
+        .try_for_each(|(item, quantity)| quantity.validate(item))
+}
+
+/// How many times what's needed fits in what we have.
+fn batches(item: &str, need: &Quantity, have: &Quantity) -> Result<usize, String> {
+    if let (Quantity::Plain(need), Quantity::Plain(have)) = (need, have) {
+        return Ok(have / need);
+    }
+    let unit = have.unit().or(need.unit());
+    let need = need.amount_in(item, unit)?;
+    let have = have.amount_in(item, unit)?;
+
+    // tolerate the rounding of the conversions
+    Ok((have / need + 1e-9).floor() as usize)
+}
+
+/// What's left of what we have, in its unit, after using what's needed `times` times.
+fn remaining(
+    item: &str,
+    have: &Quantity,
+    need: &Quantity,
+    times: usize,
+) -> Result<Quantity, String> {
+    if let (Quantity::Plain(have), Quantity::Plain(need)) = (have, need) {
+        return Ok(Quantity::Plain(have - times * need));
+    }
+    let unit = have.unit().or(need.unit());
+    let left = have.amount_in(item, unit)? - times as f64 * need.amount_in(item, unit)?;
+
+    Ok(Quantity::new(left.max(0.0), have.unit()))
+}
+
+fn bake_batches(bake_instructions: BakeInstructions) -> Result<BakeResult, String> {
+    validate(&bake_instructions.recipe)?;
+    validate(&bake_instructions.pantry)?;
 
     let cookies = bake_instructions
         .recipe
         .iter()
-        .filter_map(|(item, qtt)| {
-            if qtt > &0 {
-                Some(bake_instructions.pantry.get(item).unwrap_or(&0_usize) / qtt)
-            } else {
-                None
-            }
+        .filter(|(_, qtt)| !qtt.is_zero())
+        .map(|(item, qtt)| {
+            let available = bake_instructions
+                .pantry
+                .get(item)
+                .unwrap_or(&Quantity::Plain(0));
+            batches(item, qtt, available)
         })
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
         .min()
-        .unwrap();
+        .ok_or("The recipe needs at least one ingredient")?;
 
     let pantry = if cookies == 0 {
         bake_instructions.pantry
@@ -68,29 +115,108 @@ async fn bake(jar: CookieJar) -> Json<BakeResult> {
             .pantry
             .iter()
             .map(|(item, qtt)| {
-                (
-                    item.clone(),
-                    qtt - cookies * bake_instructions.recipe.get(item).unwrap_or(&0_usize),
-                )
+                let left = match bake_instructions.recipe.get(item) {
+                    Some(need) => remaining(item, qtt, need, cookies)?,
+                    None => qtt.clone(),
+                };
+                Ok((item.clone(), left))
             })
-            .collect::<Ingredients>()
+            .collect::<Result<Ingredients, String>>()?
     };
 
-    Json(BakeResult { cookies, pantry })
+    Ok(BakeResult { cookies, pantry })
+}
+
+#[derive(Deserialize, Debug)]
+struct ScaleRequest {
+    recipe: Ingredients,
+    cookies: usize,
+    #[serde(default)]
+    pantry: Ingredients,
+}
+
+#[derive(Serialize, Debug)]
+struct ScaleResult {
+    cookies: usize,
+    /// The recipe for all the cookies.
+    recipe: Ingredients,
+    /// What the pantry lacks for them, in the units of the recipe.
+    shopping_list: Ingredients,
+}
+
+async fn scale(
+    Json(request): Json<ScaleRequest>,
+) -> Result<Json<ScaleResult>, (StatusCode, String)> {
+    scale_recipe(request)
+        .map(Json)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))
+}
+
+fn scale_recipe(request: ScaleRequest) -> Result<ScaleResult, String> {
+    validate(&request.recipe)?;
+    validate(&request.pantry)?;
+    let cookies = request.cookies;
+
+    let mut recipe = Ingredients::new();
+    let mut shopping_list = Ingredients::new();
+    for (item, qtt) in request.recipe {
+        let have = request.pantry.get(&item).unwrap_or(&Quantity::Plain(0));
+        let (total, missing) = match (&qtt, have) {
+            (Quantity::Plain(need), Quantity::Plain(have)) => {
+                let total = need
+                    .checked_mul(cookies)
+                    .ok_or(format!("Too much {item} to count"))?;
+                (
+                    Quantity::Plain(total),
+                    Quantity::Plain(total.saturating_sub(*have)),
+                )
+            }
+            _ => {
+                let unit = qtt.unit().or(have.unit());
+                let total = qtt.amount_in(&item, unit)? * cookies as f64;
+                let missing = total - have.amount_in(&item, unit)?;
+                (
+                    Quantity::new(total, unit),
+                    Quantity::new(missing.max(0.0), unit),
+                )
+            }
+        };
+        if !missing.is_zero() {
+            shopping_list.insert(item.clone(), missing);
+        }
+        recipe.insert(item, total);
+    }
+
+    Ok(ScaleResult {
+        cookies,
+        recipe,
+        shopping_list,
+    })
+}
+
+async fn plan(
+    Json(request): Json<planner::PlanRequest>,
+) -> Result<Json<planner::Plan>, (StatusCode, String)> {
+    planner::plan(request)
+        .map(Json)
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))
 }
 
 #[cfg(test)]
 mod tests {
     use axum::http::StatusCode;
-    use axum_extra::extract::cookie::Cookie;
+    use axum_extra::extract::cookie::{Cookie, Key};
     use axum_test::TestServer;
+    use base64::engine::general_purpose;
+    use base64::Engine;
     use serde_json::json;
 
+    use super::cookies::CookieMode;
     use super::*;
 
     #[tokio::test]
     async fn task1() {
-        let app = get_day_7_router();
+        let app = get_day_7_router(CookieConfig::default());
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -111,7 +237,7 @@ mod tests {
 
     #[tokio::test]
     async fn task2() {
-        let app = get_day_7_router();
+        let app = get_day_7_router(CookieConfig::default());
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -141,7 +267,7 @@ mod tests {
 
     #[tokio::test]
     async fn task3() {
-        let app = get_day_7_router();
+        let app = get_day_7_router(CookieConfig::default());
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -165,4 +291,336 @@ mod tests {
           }
         }));
     }
+
+    #[tokio::test]
+    async fn signed_cookies() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+
+        // Run the application for testing.
+        let old_server = TestServer::new(get_day_7_router(CookieConfig {
+            keys: vec![old_key.clone()],
+            mode: CookieMode::Signed,
+            accept_legacy: false,
+        }))
+        .unwrap();
+        let server = TestServer::new(get_day_7_router(CookieConfig {
+            keys: vec![new_key, old_key],
+            mode: CookieMode::Signed,
+            accept_legacy: false,
+        }))
+        .unwrap();
+
+        // Send the request.
+        let response = old_server
+            .post("/recipe")
+            .json(&json!({"flour": 100, "chocolate chips": 20}))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let cookie = response.cookie("recipe");
+
+        // The old key is still accepted after the rotation.
+        // Send the request.
+        let response = server.get("/decode").add_cookie(cookie.clone()).await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({"flour":100,"chocolate chips":20}));
+
+        // Send the request.
+        let (signature, value) = cookie.value().split_at(1);
+        let tampered = format!("{}{value}", if signature == "A" { "B" } else { "A" });
+        let response = server
+            .get("/decode")
+            .add_cookie(Cookie::new("recipe", tampered))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+
+        // Unsigned cookies are refused without the legacy flag.
+        // Send the request.
+        let response = server
+            .get("/decode")
+            .add_cookie(Cookie::new(
+                "recipe",
+                "eyJmbG91ciI6MTAwLCJjaG9jb2xhdGUgY2hpcHMiOjIwfQ==",
+            ))
+            .await;
+
+        response.assert_status(StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn private_cookies() {
+        let app = get_day_7_router(CookieConfig {
+            keys: vec![Key::generate()],
+            mode: CookieMode::Private,
+            accept_legacy: true,
+        });
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .post("/recipe")
+            .json(&json!({
+                "recipe": {"flour": 95, "sugar": 50},
+                "pantry": {"flour": 385, "sugar": 507}
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        let cookie = response.cookie("recipe");
+        assert!(!cookie.value().starts_with("eyJ"));
+
+        // Send the request.
+        let response = server.get("/bake").add_cookie(cookie).await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "cookies": 4,
+          "pantry": { "flour": 5, "sugar": 307 }
+        }));
+    }
+
+    #[tokio::test]
+    async fn units() {
+        let app = get_day_7_router(CookieConfig::default());
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let recipe = json!({
+            "recipe": {
+                "flour": { "amount": 1, "unit": "cup" },
+                "butter": { "amount": 2, "unit": "tbsp" },
+                "eggs": { "amount": 1, "unit": "piece" }
+            },
+            "pantry": {
+                "flour": { "amount": 0.5, "unit": "kg" },
+                "butter": { "amount": 100, "unit": "g" },
+                "eggs": 3,
+                "sugar": 20
+            }
+        });
+
+        // Send the request.
+        let response = server
+            .get("/bake")
+            .add_cookie(Cookie::new(
+                "recipe",
+                general_purpose::STANDARD.encode(recipe.to_string()),
+            ))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "cookies": 3,
+          "pantry": {
+            "flour": { "amount": 0.14, "unit": "kg" },
+            "butter": { "amount": 14.875, "unit": "g" },
+            "eggs": 0,
+            "sugar": 20
+          }
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/scale")
+            .json(&json!({
+                "recipe": { "flour": { "amount": 1, "unit": "cups" }, "sugar": 50, "salt": 1 },
+                "cookies": 3,
+                "pantry": { "flour": { "amount": 200, "unit": "g" }, "sugar": 100, "salt": 5 }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+          "cookies": 3,
+          "recipe": {
+            "flour": { "amount": 3.0, "unit": "cup" },
+            "sugar": 150,
+            "salt": 3
+          },
+          "shopping_list": {
+            "flour": { "amount": 1.333333, "unit": "cup" },
+            "sugar": 50
+          }
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/scale")
+            .json(&json!({
+                "recipe": { "eggs": { "amount": 1, "unit": "piece" } },
+                "cookies": 3,
+                "pantry": { "eggs": { "amount": 1, "unit": "kg" } }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/scale")
+            .json(&json!({
+                "recipe": { "flour": { "amount": 1, "unit": "cup" } },
+                "cookies": 1,
+                "pantry": { "flour": 500 }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("flour can't be counted and measured at once");
+    }
+
+    #[tokio::test]
+    async fn plan() {
+        let app = get_day_7_router(CookieConfig::default());
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Greedily baking the most valuable cookies leaves value behind.
+        // Send the request.
+        let response = server
+            .post("/plan")
+            .json(&json!({
+                "recipes": [
+                    { "name": "chocolate", "recipe": { "flour": 5, "chocolate": 3 }, "value": 5 },
+                    { "name": "shortbread", "recipe": { "flour": 4, "butter": 2 }, "value": 4 },
+                    { "name": "brownie", "recipe": { "chocolate": 2, "butter": 1 }, "value": 3 }
+                ],
+                "pantry": { "flour": 20, "chocolate": 9, "butter": 7, "sugar": 3 }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({
+            "value": 22.0,
+            "optimal": true,
+            "recipes": [
+                { "name": "chocolate", "count": 1 },
+                { "name": "shortbread", "count": 2 },
+                { "name": "brownie", "count": 3 }
+            ],
+            "pantry": { "flour": 7, "chocolate": 0, "butter": 0, "sugar": 3 }
+        }));
+
+        // Send the request.
+        let response = server
+            .post("/plan")
+            .json(&json!({
+                "recipes": [{ "name": "air", "recipe": { "flour": 0 } }],
+                "pantry": { "flour": 20 }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/plan")
+            .json(&json!({
+                "recipes": [{ "name": "plain", "recipe": { "flour": 1 } }],
+                "pantry": { "flour": 1_000_000_000_000_u64 }
+            }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        let plan = response.json::<serde_json::Value>();
+        assert_eq!(plan["optimal"], true);
+        assert_eq!(plan["recipes"][0]["count"], 1_000_000_000_000_u64);
+    }
+
+    #[tokio::test]
+    async fn recipe_sources() {
+        let app = get_day_7_router(CookieConfig::default());
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        let recipe = json!({"flour???": 100});
+        let url_safe = general_purpose::URL_SAFE_NO_PAD.encode(recipe.to_string());
+        assert!(url_safe.contains('_'));
+
+        // Send the request.
+        let response = server
+            .get("/decode")
+            .add_header("x-recipe".parse().unwrap(), url_safe.parse().unwrap())
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&recipe);
+
+        // The same lenient base64 in a legacy cookie.
+        // Send the request.
+        let response = server
+            .get("/decode")
+            .add_cookie(Cookie::new("recipe", url_safe.clone()))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&recipe);
+
+        // Send the request.
+        let response = server
+            .get("/decode")
+            .add_query_param(
+                "recipe",
+                general_purpose::STANDARD.encode(recipe.to_string()),
+            )
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&recipe);
+
+        // Send the request.
+        let response = server
+            .post("/bake")
+            .json(&json!({ "recipe": { "flour": 95 }, "pantry": { "flour": 385 } }))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({ "cookies": 4, "pantry": { "flour": 5 } }));
+
+        // Send the request.
+        let response = server
+            .get("/decode")
+            .add_header("x-recipe".parse().unwrap(), "e30*".parse().unwrap())
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response
+            .text()
+            .starts_with("Invalid recipe in the X-Recipe header: invalid base64"));
+
+        // Send the request.
+        let response = server
+            .get("/bake")
+            .add_query_param("recipe", general_purpose::STANDARD.encode("[1, 2]"))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert!(response
+            .text()
+            .starts_with("Invalid recipe in the recipe query parameter: invalid JSON"));
+
+        // Send the request.
+        let response = server.get("/bake").await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,142 @@
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::cookie::{Cookie, Key};
+use axum_extra::extract::{CookieJar, PrivateCookieJar, SignedCookieJar};
+use base64::engine::general_purpose;
+use base64::Engine;
+use serde_json::{Map, Value};
+use shuttle_service::SecretStore;
+
+use super::input::decode;
+
+pub const RECIPE_COOKIE: &str = "recipe";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CookieMode {
+    /// Readable by the client, but not modifiable.
+    #[default]
+    Signed,
+    /// Encrypted as well.
+    Private,
+}
+
+/// How the recipe cookies are issued and checked.
+#[derive(Clone)]
+pub struct CookieConfig {
+    /// The first key issues cookies, all of them are accepted so keys can be rotated.
+    pub keys: Vec<Key>,
+    pub mode: CookieMode,
+    /// Also accept the plain base64 cookies of the original challenge.
+    pub accept_legacy: bool,
+}
+
+impl Default for CookieConfig {
+    /// A key of its own, the cookies of the original challenge still being accepted.
+    fn default() -> Self {
+        CookieConfig {
+            keys: vec![Key::generate()],
+            mode: CookieMode::default(),
+            accept_legacy: true,
+        }
+    }
+}
+
+impl CookieConfig {
+    /// Reads `RECIPE_COOKIE_KEYS` (comma separated base64 keys of at least 64 bytes),
+    /// `RECIPE_COOKIE_MODE` (`signed` or `private`) and `RECIPE_COOKIE_LEGACY`.
+    /// Without keys, one is generated and the cookies don't outlive the server.
+    /// Legacy cookies can be forged, `RECIPE_COOKIE_LEGACY = "false"` refuses them.
+    pub fn from_secrets(secrets: &SecretStore) -> CookieConfig {
+        let keys = match secrets
+            .get("RECIPE_COOKIE_KEYS")
+            .filter(|keys| !keys.trim().is_empty())
+        {
+            Some(keys) => keys
+                .split(',')
+                .map(|key| {
+                    let bytes = general_purpose::STANDARD
+                        .decode(key.trim())
+                        .expect("RECIPE_COOKIE_KEYS holds invalid base64");
+                    Key::try_from(bytes.as_slice()).expect("Cookie keys need 64 bytes")
+                })
+                .collect(),
+            None => {
+                tracing::warn!(
+                    "No RECIPE_COOKIE_KEYS secret, recipe cookies won't outlive a restart"
+                );
+                vec![Key::generate()]
+            }
+        };
+        let mode = match secrets.get("RECIPE_COOKIE_MODE").as_deref() {
+            Some("private") => CookieMode::Private,
+            Some("signed") | None => CookieMode::Signed,
+            Some(mode) => panic!("Unknown RECIPE_COOKIE_MODE '{mode}'"),
+        };
+        let accept_legacy = secrets
+            .get("RECIPE_COOKIE_LEGACY")
+            .map_or(true, |flag| flag != "false");
+
+        CookieConfig {
+            keys,
+            mode,
+            accept_legacy,
+        }
+    }
+
+    /// The base64 recipe of the first cookie one of the keys vouches for.
+    fn verified(&self, headers: &HeaderMap) -> Option<String> {
+        self.keys.iter().find_map(|key| {
+            let cookie = match self.mode {
+                CookieMode::Signed => {
+                    SignedCookieJar::from_headers(headers, key.clone()).get(RECIPE_COOKIE)
+                }
+                CookieMode::Private => {
+                    PrivateCookieJar::from_headers(headers, key.clone()).get(RECIPE_COOKIE)
+                }
+            };
+            cookie.map(|cookie| cookie.value().to_string())
+        })
+    }
+
+    /// The base64 recipe of the request cookie, or 401 when it was tampered with.
+    pub fn recipe(&self, headers: &HeaderMap) -> Result<String, (StatusCode, String)> {
+        let Some(cookie) = CookieJar::from_headers(headers).get(RECIPE_COOKIE).cloned() else {
+            return Err((StatusCode::BAD_REQUEST, "Missing recipe cookie".to_string()));
+        };
+        if let Some(recipe) = self.verified(headers) {
+            return Ok(recipe);
+        }
+
+        let legacy = self.accept_legacy
+            && decode(cookie.value())
+                .is_ok_and(|bytes| serde_json::from_slice::<Value>(&bytes).is_ok());
+        if legacy {
+            Ok(cookie.value().to_string())
+        } else {
+            Err((
+                StatusCode::UNAUTHORIZED,
+                "Invalid recipe cookie".to_string(),
+            ))
+        }
+    }
+}
+
+/// Issues a recipe cookie holding the JSON body.
+pub async fn issue_recipe(
+    State(config): State<CookieConfig>,
+    Json(recipe): Json<Map<String, Value>>,
+) -> Response {
+    let encoded = general_purpose::STANDARD.encode(serde_json::to_vec(&recipe).unwrap());
+    let mut cookie = Cookie::new(RECIPE_COOKIE, encoded);
+    cookie.set_http_only(true);
+    let key = config.keys[0].clone();
+
+    match config.mode {
+        CookieMode::Signed => (SignedCookieJar::new(key).add(cookie), Json(recipe)).into_response(),
+        CookieMode::Private => {
+            (PrivateCookieJar::new(key).add(cookie), Json(recipe)).into_response()
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRef, FromRequest, Query, Request};
+use axum::http::StatusCode;
+use axum_extra::extract::CookieJar;
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use serde::de::DeserializeOwned;
+
+use super::cookies::{CookieConfig, RECIPE_COOKIE};
+
+const RECIPE_HEADER: &str = "x-recipe";
+const RECIPE_PARAMETER: &str = "recipe";
+
+const ANY_PADDING: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const STANDARD: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, ANY_PADDING);
+const URL_SAFE: GeneralPurpose = GeneralPurpose::new(&alphabet::URL_SAFE, ANY_PADDING);
+
+#[derive(Debug, Clone, Copy)]
+enum Source {
+    Cookie,
+    Header,
+    Query,
+    Body,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Source::Cookie => "recipe cookie",
+            Source::Header => "X-Recipe header",
+            Source::Query => "recipe query parameter",
+            Source::Body => "request body",
+        })
+    }
+}
+
+/// Standard or URL-safe base64, padded or not.
+pub fn decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let engine = if encoded.contains(['-', '_']) {
+        &URL_SAFE
+    } else {
+        &STANDARD
+    };
+    engine
+        .decode(encoded.trim())
+        .map_err(|error| format!("invalid base64 ({error})"))
+}
+
+/// A recipe read from the first of the cookie, the `X-Recipe` header, the `recipe` query
+/// parameter (all base64 JSON) or the JSON body that the request has.
+pub struct RecipeInput<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for RecipeInput<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+    CookieConfig: FromRef<S>,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let invalid = |source: Source, reason: String| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid recipe in the {source}: {reason}"),
+            )
+        };
+        let (parts, body) = request.into_parts();
+        let query = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map(|Query(query)| query)
+            .unwrap_or_default();
+
+        let (source, bytes) = if CookieJar::from_headers(&parts.headers)
+            .get(RECIPE_COOKIE)
+            .is_some()
+        {
+            // tampered cookies are still refused with a 401
+            let encoded = CookieConfig::from_ref(state).recipe(&parts.headers)?;
+            let bytes = decode(&encoded).map_err(|reason| invalid(Source::Cookie, reason))?;
+            (Source::Cookie, bytes)
+        } else if let Some(header) = parts.headers.get(RECIPE_HEADER) {
+            let bytes = header
+                .to_str()
+                .map_err(|_| "not visible ASCII".to_string())
+                .and_then(decode)
+                .map_err(|reason| invalid(Source::Header, reason))?;
+            (Source::Header, bytes)
+        } else if let Some(parameter) = query.get(RECIPE_PARAMETER) {
+            // a '+' left unescaped in the query reads as a space
+            let bytes = decode(&parameter.replace(' ', "+"))
+                .map_err(|reason| invalid(Source::Query, reason))?;
+            (Source::Query, bytes)
+        } else {
+            let bytes = Bytes::from_request(Request::from_parts(parts, body), state)
+                .await
+                .map_err(|rejection| invalid(Source::Body, rejection.body_text()))?;
+            if bytes.is_empty() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "No recipe in the cookie, the X-Recipe header, the query or the body"
+                        .to_string(),
+                ));
+            }
+            (Source::Body, bytes.to_vec())
+        };
+
+        serde_json::from_slice(&bytes)
+            .map(RecipeInput)
+            .map_err(|error| invalid(source, format!("invalid JSON ({error})")))
+    }
+}
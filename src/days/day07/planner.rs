@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use super::{remaining, validate, Ingredients, Quantity};
+
+const MAX_RECIPES: usize = 50;
+/// Past this many nodes, the best plan found so far is returned as is.
+const MAX_NODES: usize = 100_000;
+const EPSILON: f64 = 1e-9;
+
+#[derive(Deserialize, Debug)]
+pub struct PlanRequest {
+    recipes: Vec<PlannedRecipe>,
+    pantry: Ingredients,
+}
+
+#[derive(Deserialize, Debug)]
+struct PlannedRecipe {
+    name: String,
+    recipe: Ingredients,
+    /// What a batch is worth, batches all count the same by default.
+    #[serde(default = "default_value")]
+    value: f64,
+}
+
+fn default_value() -> f64 {
+    1.0
+}
+
+#[derive(Serialize, Debug)]
+pub struct Plan {
+    value: f64,
+    /// Whether the search went through, or stopped early on a large problem.
+    optimal: bool,
+    recipes: Vec<Batches>,
+    pantry: Ingredients,
+}
+
+#[derive(Serialize, Debug)]
+struct Batches {
+    name: String,
+    count: usize,
+}
+
+/// Branch and bound over the batch counts, the recipes taken by decreasing value.
+struct Solver {
+    values: Vec<f64>,
+    /// Per recipe, what a batch takes of each ingredient.
+    needs: Vec<Vec<f64>>,
+    best_value: f64,
+    best_counts: Vec<usize>,
+    nodes: usize,
+}
+
+impl Solver {
+    fn max_batches(&self, recipe: usize, have: &[f64]) -> f64 {
+        self.needs[recipe]
+            .iter()
+            .zip(have)
+            .filter(|(need, _)| **need > 0.0)
+            .map(|(need, have)| (have / need + EPSILON).floor())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// At most what the recipes from `from` can still add.
+    fn bound(&self, from: usize, have: &[f64]) -> f64 {
+        let recipes = from..self.values.len();
+        let separately: f64 = recipes
+            .clone()
+            .map(|recipe| self.values[recipe] * self.max_batches(recipe, have))
+            .sum();
+
+        // each ingredient alone, given to the recipes making the most of it
+        let by_ingredient = have
+            .iter()
+            .enumerate()
+            .filter_map(|(ingredient, have)| {
+                recipes
+                    .clone()
+                    .filter(|&recipe| self.values[recipe] > 0.0)
+                    .map(|recipe| {
+                        let need = self.needs[recipe][ingredient];
+                        (need > 0.0).then(|| have * self.values[recipe] / need)
+                    })
+                    .try_fold(0.0_f64, |best, value| Some(best.max(value?)))
+            })
+            .fold(f64::INFINITY, f64::min);
+
+        separately.min(by_ingredient)
+    }
+
+    fn search(&mut self, recipe: usize, counts: &mut Vec<usize>, have: &mut [f64], value: f64) {
+        if self.nodes >= MAX_NODES {
+            return;
+        }
+        self.nodes += 1;
+
+        if value > self.best_value + EPSILON {
+            self.best_value = value;
+            self.best_counts = counts.clone();
+        }
+        if recipe == self.values.len()
+            || value + self.bound(recipe, have) <= self.best_value + EPSILON
+        {
+            return;
+        }
+
+        // the most batches first, to find good plans early
+        for count in (0..=self.max_batches(recipe, have) as usize).rev() {
+            // fewer batches can't beat what was found since, however many there are left
+            if self.nodes >= MAX_NODES
+                || value + self.bound(recipe, have) <= self.best_value + EPSILON
+            {
+                break;
+            }
+            for (have, need) in have.iter_mut().zip(&self.needs[recipe]) {
+                *have -= need * count as f64;
+            }
+            let value = value + self.values[recipe] * count as f64;
+            if value + self.bound(recipe + 1, have) > self.best_value + EPSILON {
+                counts.push(count);
+                self.search(recipe + 1, counts, have, value);
+                counts.pop();
+            } else {
+                // pruned counts use up the budget too, or there could be no end to them
+                self.nodes += 1;
+            }
+            for (have, need) in have.iter_mut().zip(&self.needs[recipe]) {
+                *have += need * count as f64;
+            }
+        }
+    }
+}
+
+pub fn plan(request: PlanRequest) -> Result<Plan, String> {
+    if request.recipes.len() > MAX_RECIPES {
+        return Err(format!("At most {MAX_RECIPES} recipes can be planned"));
+    }
+    validate(&request.pantry)?;
+
+    // every ingredient of a recipe, in the unit of the pantry
+    let mut ingredients: Vec<&String> = Vec::new();
+    for planned in &request.recipes {
+        validate(&planned.recipe)?;
+        if !planned.value.is_finite() || planned.value < 0.0 {
+            return Err(format!("Invalid value for {}", planned.name));
+        }
+        if planned.recipe.values().all(Quantity::is_zero) {
+            return Err(format!("{} needs at least one ingredient", planned.name));
+        }
+        for item in planned.recipe.keys() {
+            if !ingredients.contains(&item) {
+                ingredients.push(item);
+            }
+        }
+    }
+    let stock: Vec<&Quantity> = ingredients
+        .iter()
+        .map(|&item| request.pantry.get(item).unwrap_or(&Quantity::Plain(0)))
+        .collect();
+    let mut have = ingredients
+        .iter()
+        .zip(&stock)
+        .map(|(item, have)| have.amount_in(item, have.unit()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut order: Vec<usize> = (0..request.recipes.len()).collect();
+    order.sort_by(|&a, &b| {
+        request.recipes[b]
+            .value
+            .total_cmp(&request.recipes[a].value)
+    });
+    let needs = order
+        .iter()
+        .map(|&index| {
+            let recipe = &request.recipes[index].recipe;
+            ingredients
+                .iter()
+                .zip(&stock)
+                .map(|(item, have)| match recipe.get(*item) {
+                    Some(need) if have.is_zero() => need.amount_in(item, need.unit()),
+                    Some(need) => need.amount_in(item, have.unit()),
+                    None => Ok(0.0),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut solver = Solver {
+        values: order
+            .iter()
+            .map(|&index| request.recipes[index].value)
+            .collect(),
+        needs,
+        best_value: 0.0,
+        best_counts: vec![0; order.len()],
+        nodes: 0,
+    };
+    solver.search(0, &mut Vec::with_capacity(order.len()), &mut have, 0.0);
+    let optimal = solver.nodes < MAX_NODES;
+
+    let mut counts = vec![0; request.recipes.len()];
+    for (position, &index) in order.iter().enumerate() {
+        counts[index] = solver.best_counts.get(position).copied().unwrap_or(0);
+    }
+
+    let mut pantry = request.pantry;
+    for (planned, &count) in request.recipes.iter().zip(&counts) {
+        if count == 0 {
+            continue;
+        }
+        for (item, need) in &planned.recipe {
+            if let Some(have) = pantry.get_mut(item) {
+                *have = remaining(item, have, need, count)?;
+            }
+        }
+    }
+
+    Ok(Plan {
+        value: solver.best_value,
+        optimal,
+        recipes: request
+            .recipes
+            .into_iter()
+            .zip(counts)
+            .map(|(planned, count)| Batches {
+                name: planned.name,
+                count,
+            })
+            .collect(),
+        pantry,
+    })
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+/// Grams in a cup of the ingredients that can be weighed as well as measured by volume.
+const GRAMS_PER_CUP: [(&str, f64); 12] = [
+    ("flour", 120.0),
+    ("sugar", 200.0),
+    ("brown sugar", 220.0),
+    ("butter", 227.0),
+    ("baking powder", 192.0),
+    ("baking soda", 220.0),
+    ("salt", 288.0),
+    ("cocoa", 85.0),
+    ("chocolate chips", 170.0),
+    ("oats", 90.0),
+    ("milk", 245.0),
+    ("water", 236.6),
+];
+
+const MILLILITRES_PER_CUP: f64 = 236.588;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    #[serde(rename = "g", alias = "gram", alias = "grams")]
+    Gram,
+    #[serde(rename = "kg", alias = "kilogram", alias = "kilograms")]
+    Kilogram,
+    #[serde(rename = "ml", alias = "millilitre", alias = "millilitres")]
+    Millilitre,
+    #[serde(rename = "tsp", alias = "teaspoon", alias = "teaspoons")]
+    Teaspoon,
+    #[serde(rename = "tbsp", alias = "tablespoon", alias = "tablespoons")]
+    Tablespoon,
+    #[serde(rename = "cup", alias = "cups")]
+    Cup,
+    #[serde(rename = "piece", alias = "pieces")]
+    Piece,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    Mass,
+    Volume,
+    Count,
+}
+
+impl Unit {
+    fn dimension(self) -> Dimension {
+        match self {
+            Unit::Gram | Unit::Kilogram => Dimension::Mass,
+            Unit::Millilitre | Unit::Teaspoon | Unit::Tablespoon | Unit::Cup => Dimension::Volume,
+            Unit::Piece => Dimension::Count,
+        }
+    }
+
+    /// In grams, millilitres or pieces.
+    fn factor(self) -> f64 {
+        match self {
+            Unit::Gram | Unit::Millilitre | Unit::Piece => 1.0,
+            Unit::Kilogram => 1000.0,
+            Unit::Teaspoon => MILLILITRES_PER_CUP / 48.0,
+            Unit::Tablespoon => MILLILITRES_PER_CUP / 16.0,
+            Unit::Cup => MILLILITRES_PER_CUP,
+        }
+    }
+}
+
+fn density(item: &str) -> Result<f64, String> {
+    GRAMS_PER_CUP
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(item))
+        .map(|(_, grams)| grams / MILLILITRES_PER_CUP)
+        .ok_or(format!(
+            "No density known for {item}, it can't be weighed and measured"
+        ))
+}
+
+fn convert(item: &str, amount: f64, from: Unit, to: Unit) -> Result<f64, String> {
+    let base = amount * from.factor();
+    let base = match (from.dimension(), to.dimension()) {
+        (a, b) if a == b => base,
+        (Dimension::Volume, Dimension::Mass) => base * density(item)?,
+        (Dimension::Mass, Dimension::Volume) => base / density(item)?,
+        _ => return Err(format!("{item} can't be counted and measured at once")),
+    };
+    Ok(base / to.factor())
+}
+
+/// A plain number as in the original recipes, or an amount of some unit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Quantity {
+    Plain(usize),
+    Measured { amount: f64, unit: Unit },
+}
+
+impl Quantity {
+    /// Plain numbers are in no unit, so rounded to a plain number again.
+    pub fn new(amount: f64, unit: Option<Unit>) -> Quantity {
+        match unit {
+            Some(unit) => Quantity::Measured {
+                // no float noise in the responses
+                amount: (amount * 1e6).round() / 1e6,
+                unit,
+            },
+            None => Quantity::Plain(amount.round() as usize),
+        }
+    }
+
+    pub fn unit(&self) -> Option<Unit> {
+        match self {
+            Quantity::Plain(_) => None,
+            Quantity::Measured { unit, .. } => Some(*unit),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Quantity::Plain(quantity) => *quantity == 0,
+            Quantity::Measured { amount, .. } => *amount == 0.0,
+        }
+    }
+
+    pub fn validate(&self, item: &str) -> Result<(), String> {
+        match self {
+            Quantity::Measured { amount, .. } if !amount.is_finite() || *amount < 0.0 => {
+                Err(format!("Invalid amount of {item}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The amount in `unit`, plain numbers and no unit counting pieces,
+    /// so that they can't be compared with a weight or a volume.
+    pub fn amount_in(&self, item: &str, unit: Option<Unit>) -> Result<f64, String> {
+        let to = unit.unwrap_or(Unit::Piece);
+        match self {
+            // nothing of it is nothing in any unit
+            Quantity::Plain(0) => Ok(0.0),
+            Quantity::Plain(quantity) => convert(item, *quantity as f64, Unit::Piece, to),
+            Quantity::Measured { amount, unit: from } => convert(item, *amount, *from, to),
+        }
+    }
+}
@@ -1,60 +1,152 @@
-use axum::extract::{Path, State};
-use axum::http::StatusCode;
-use axum::routing::get;
-use axum::Router;
-use reqwest::Client;
-use serde_json::Value;
-
-pub fn get_day_8_router() -> Router {
-    let client = Client::new();
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+
+pub use client::PokemonClient;
+use physics::{DropParams, Impact};
+use pokemon::{PokemonInfo, Units};
+
+mod client;
+mod physics;
+mod pokemon;
+
+const MAX_BATCH: usize = 100;
+
+pub fn get_day_8_router(client: PokemonClient) -> Router {
     Router::new()
-        .route("/weight/:poke_number", get(poke_weight))
-        .route("/drop/:poke_number", get(drop))
+        .route("/weight/:pokemon", get(poke_weight))
+        .route("/drop/:pokemon", get(drop))
+        .route("/drop", post(drop_batch))
+        .route("/pokemon/:pokemon", get(pokemon_info))
         .with_state(client)
 }
 
-async fn drop(Path(poke_number): Path<i32>, State(client): State<Client>) -> (StatusCode, String) {
-    let weight = get_weight_in_kilo(poke_number, client).await;
-    let height: f64 = 10.0;
-    let gravity: f64 = 9.825;
-    let momentum = (gravity * height * 2.0).sqrt() * weight;
+/// The momentum as plain text like the challenge expects, unless JSON is accepted.
+async fn drop(
+    Path(pokemon): Path<String>,
+    Query(params): Query<DropParams>,
+    headers: HeaderMap,
+    State(client): State<PokemonClient>,
+) -> Result<Response, (StatusCode, String)> {
+    let fall = params
+        .fall()
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))?;
+    let weight = client.pokemon(&pokemon).await?.weight_in_kilo();
+    let impact = fall.impact(weight);
 
-    (StatusCode::OK, momentum.to_string())
+    let wants_json = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if wants_json {
+        Ok(Json(impact).into_response())
+    } else {
+        Ok(impact.momentum.to_string().into_response())
+    }
+}
+
+/// A Pokédex number or a name.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum PokemonId {
+    Number(u32),
+    Name(String),
+}
+
+impl PokemonId {
+    fn to_key(&self) -> String {
+        match self {
+            PokemonId::Number(number) => number.to_string(),
+            PokemonId::Name(name) => name.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct BatchDrop {
+    pokemon: PokemonId,
+    /// In kg.
+    weight: f64,
+    #[serde(flatten)]
+    impact: Impact,
+}
+
+async fn drop_batch(
+    Query(params): Query<DropParams>,
+    State(client): State<PokemonClient>,
+    Json(pokemons): Json<Vec<PokemonId>>,
+) -> Result<Json<Vec<BatchDrop>>, (StatusCode, String)> {
+    if pokemons.len() > MAX_BATCH {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_BATCH} Pokémon can be dropped at once"),
+        ));
+    }
+    let fall = params
+        .fall()
+        .map_err(|error| (StatusCode::BAD_REQUEST, error))?;
+
+    let drops = pokemons.into_iter().map(|pokemon| {
+        let client = &client;
+        async move {
+            let weight = client
+                .pokemon(&pokemon.to_key())
+                .await
+                .map_err(|(status, error)| (status, format!("{}: {error}", pokemon.to_key())))?
+                .weight_in_kilo();
+            Ok::<_, (StatusCode, String)>(BatchDrop {
+                pokemon,
+                weight,
+                impact: fall.impact(weight),
+            })
+        }
+    });
+    try_join_all(drops).await.map(Json)
 }
 
 async fn poke_weight(
-    Path(poke_number): Path<i32>,
-    State(client): State<Client>,
-) -> (StatusCode, String) {
-    let weight = get_weight_in_kilo(poke_number, client).await;
-    (StatusCode::OK, weight.to_string())
+    Path(pokemon): Path<String>,
+    State(client): State<PokemonClient>,
+) -> Result<String, (StatusCode, String)> {
+    let weight = client.pokemon(&pokemon).await?.weight_in_kilo();
+    Ok(weight.to_string())
 }
 
-async fn get_weight_in_kilo(poke_number: i32, client: Client) -> f64 {
-    let weight = if let Ok(response) = client
-        .get(format!("https://pokeapi.co/api/v2/pokemon/{poke_number}"))
-        .send()
-        .await
-    {
-        let data: Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
-        let weight_in_hectogram = data.get("weight").unwrap();
-        weight_in_hectogram.as_f64().unwrap() / 10.0 // weight_in_kilogram
-    } else {
-        0.0
-    };
-    weight
+#[derive(Deserialize, Debug)]
+struct InfoParams {
+    #[serde(default)]
+    units: Units,
+}
+
+async fn pokemon_info(
+    Path(pokemon): Path<String>,
+    Query(params): Query<InfoParams>,
+    State(client): State<PokemonClient>,
+) -> Result<Json<PokemonInfo>, (StatusCode, String)> {
+    let pokemon = client.pokemon(&pokemon).await?;
+    Ok(Json(PokemonInfo::new(&pokemon, params.units)))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
     use axum::http::StatusCode;
     use axum_test::TestServer;
+    use serde_json::json;
 
     use super::*;
 
+    const FIXTURES: &str = "assets/pokeapi";
+
     #[tokio::test]
     async fn task1() {
-        let app = get_day_8_router();
+        let app = get_day_8_router(PokemonClient::offline(FIXTURES));
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -69,7 +161,7 @@ mod tests {
 
     #[tokio::test]
     async fn task2() {
-        let app = get_day_8_router();
+        let app = get_day_8_router(PokemonClient::offline(FIXTURES));
 
         // Run the application for testing.
         let server = TestServer::new(app).unwrap();
@@ -81,4 +173,162 @@ mod tests {
 
         response.assert_text(84.10707461325713.to_string());
     }
+
+    #[tokio::test]
+    async fn unknown_pokemon() {
+        let app = get_day_8_router(PokemonClient::offline(FIXTURES));
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server.get("/weight/100000").await;
+
+        response.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn physics() {
+        let app = get_day_8_router(PokemonClient::offline(FIXTURES));
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        let response = server
+            .get("/drop/25")
+            .add_query_param("height", 20)
+            .add_query_param("planet", "moon")
+            .add_header(header::ACCEPT, "application/json".parse().unwrap())
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        let impact = response.json::<serde_json::Value>();
+        // from 20 m on the Moon, so a velocity of √(2·1.62·20) m/s
+        for (field, expected) in [
+            ("velocity", 8.0498447),
+            ("momentum", 48.2990683),
+            ("energy", 194.4),
+            ("time", 4.9690399),
+        ] {
+            assert!((impact[field].as_f64().unwrap() - expected).abs() < 1e-6);
+        }
+
+        // Send the request.
+        let response = server
+            .get("/drop/25")
+            .add_query_param("gravity", 9.8)
+            .add_query_param("planet", "earth")
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .post("/drop")
+            .add_query_param("drag", 0.1)
+            .json(&json!([25, "Pikachu"]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        let drops = response.json::<serde_json::Value>();
+        assert_eq!(drops[0]["pokemon"], 25);
+        assert_eq!(drops[1]["pokemon"], "Pikachu");
+        assert_eq!(drops[0]["weight"], 6.0);
+        assert_eq!(drops[0]["velocity"], drops[1]["velocity"]);
+        assert!(drops[0]["velocity"].as_f64().unwrap() < 14.0);
+
+        // Send the request.
+        let response = server.post("/drop").json(&json!([25, "missingno"])).await;
+
+        response.assert_status(StatusCode::NOT_FOUND);
+        response.assert_text("missingno: No such Pokémon");
+    }
+
+    #[tokio::test]
+    async fn pokemon_by_name() {
+        let app = get_day_8_router(PokemonClient::offline(FIXTURES));
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        server.get("/weight/pikachu").await.assert_text("6");
+
+        // Send the request.
+        let response = server.get("/pokemon/Pikachu").await;
+
+        response.assert_status(StatusCode::OK);
+        response.assert_json(&json!({
+            "id": 25,
+            "name": "pikachu",
+            "weight": {"value": 6.0, "unit": "kg"},
+            "height": {"value": 0.4, "unit": "m"},
+            "types": ["electric"],
+            "base_stats": {
+                "hp": 35,
+                "attack": 55,
+                "defense": 40,
+                "special-attack": 50,
+                "special-defense": 50,
+                "speed": 90
+            }
+        }));
+
+        // Send the request.
+        let response = server
+            .get("/pokemon/25")
+            .add_query_param("units", "imperial")
+            .await;
+
+        response.assert_status(StatusCode::OK);
+        let info = response.json::<serde_json::Value>();
+        assert_eq!(info["weight"], json!({"value": 13.23, "unit": "lb"}));
+        assert_eq!(info["height"], json!({"value": 1.31, "unit": "ft"}));
+
+        // Send the request.
+        let response = server
+            .get("/pokemon/25")
+            .add_query_param("units", "furlongs")
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn retried_and_cached() {
+        // A PokeAPI failing once, then answering from the fixtures.
+        let hits = Arc::new(AtomicUsize::new(0));
+        let upstream = Router::new()
+            .route(
+                "/pokemon/25",
+                get(|State(hits): State<Arc<AtomicUsize>>| async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                        Err(StatusCode::SERVICE_UNAVAILABLE)
+                    } else {
+                        Ok(include_str!("../../assets/pokeapi/pokemon/25.json"))
+                    }
+                }),
+            )
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, upstream).await });
+
+        let app = get_day_8_router(PokemonClient::new(&base_url));
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Send the request.
+        server.get("/weight/25").await.assert_text("6");
+
+        // Send the request.
+        server
+            .get("/drop/25")
+            .await
+            .assert_text(84.10707461325713.to_string());
+
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
 }
@@ -0,0 +1,177 @@
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::http::StatusCode;
+use lru::LruCache;
+use reqwest::Client;
+
+use super::pokemon::Pokemon;
+
+pub const DEFAULT_BASE_URL: &str = "https://pokeapi.co/api/v2";
+
+const CACHE_SIZE: usize = 256;
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const TIMEOUT: Duration = Duration::from_secs(10);
+const RETRIES: u32 = 3;
+/// Doubled after each failed attempt.
+const BACKOFF: Duration = Duration::from_millis(100);
+
+/// Answers by resource path, with when they were fetched.
+type Cache = LruCache<String, (Instant, Arc<Pokemon>)>;
+
+#[derive(Debug, Clone)]
+enum Source {
+    Remote(String),
+    /// `pokemon/{id}.json` files, as PokeAPI would answer them.
+    Fixtures(PathBuf),
+}
+
+/// PokeAPI, or a local copy of it, with the answers cached for a while.
+#[derive(Clone)]
+pub struct PokemonClient {
+    http: Client,
+    source: Source,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl PokemonClient {
+    fn with_source(source: Source) -> PokemonClient {
+        PokemonClient {
+            http: Client::builder().timeout(TIMEOUT).build().unwrap(),
+            source,
+            cache: Arc::new(Mutex::new(LruCache::new(
+                NonZeroUsize::new(CACHE_SIZE).unwrap(),
+            ))),
+        }
+    }
+
+    /// Fetches from a PokeAPI at `base_url`, e.g. [`DEFAULT_BASE_URL`].
+    pub fn new(base_url: &str) -> PokemonClient {
+        PokemonClient::with_source(Source::Remote(base_url.trim_end_matches('/').to_string()))
+    }
+
+    /// Never goes online, reads the fixtures under `directory` instead.
+    pub fn offline(directory: impl Into<PathBuf>) -> PokemonClient {
+        PokemonClient::with_source(Source::Fixtures(directory.into()))
+    }
+
+    /// Reads `POKEAPI_FIXTURES` (a fixture directory, to run offline) or else
+    /// `POKEAPI_BASE_URL`, which defaults to the public PokeAPI.
+    pub fn from_env() -> PokemonClient {
+        match std::env::var("POKEAPI_FIXTURES") {
+            Ok(directory) => PokemonClient::offline(directory),
+            Err(_) => PokemonClient::new(
+                &std::env::var("POKEAPI_BASE_URL").unwrap_or(DEFAULT_BASE_URL.to_string()),
+            ),
+        }
+    }
+
+    /// A Pokémon by Pokédex number or name, 404 when there is none.
+    pub async fn pokemon(&self, id_or_name: &str) -> Result<Arc<Pokemon>, (StatusCode, String)> {
+        let id_or_name = id_or_name.trim().to_lowercase();
+        if id_or_name.is_empty() || id_or_name.contains(['/', '.', '?', '#']) {
+            return Err((StatusCode::NOT_FOUND, "No such Pokémon".to_string()));
+        }
+        let key = format!("pokemon/{id_or_name}");
+        if let Some((fetched, data)) = self.cache.lock().unwrap().get(&key) {
+            if fetched.elapsed() < CACHE_TTL {
+                return Ok(data.clone());
+            }
+        }
+
+        let data = Arc::new(match &self.source {
+            Source::Remote(base_url) => self.fetch(&format!("{base_url}/{key}")).await?,
+            Source::Fixtures(directory) => find_fixture(directory, &id_or_name)?,
+        });
+        self.cache
+            .lock()
+            .unwrap()
+            .put(key, (Instant::now(), data.clone()));
+        Ok(data)
+    }
+
+    /// Retries what may go better later: no answer or a cut off one, server errors
+    /// and rate limiting.
+    async fn fetch(&self, url: &str) -> Result<Pokemon, (StatusCode, String)> {
+        let mut backoff = BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let error = match self.http.get(url).send().await {
+                Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => {
+                    return Err((StatusCode::NOT_FOUND, "No such Pokémon".to_string()));
+                }
+                Ok(response) if response.status().is_success() => match response.text().await {
+                    Ok(text) => {
+                        return serde_json::from_str(&text).map_err(|error| {
+                            (
+                                StatusCode::BAD_GATEWAY,
+                                format!("Invalid PokeAPI response: {error}"),
+                            )
+                        });
+                    }
+                    // cut off while reading the body
+                    Err(error) => format!("PokeAPI response was interrupted: {error}"),
+                },
+                Ok(response)
+                    if response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    format!("PokeAPI answered {}", response.status())
+                }
+                Ok(response) => {
+                    return Err((
+                        StatusCode::BAD_GATEWAY,
+                        format!("PokeAPI answered {}", response.status()),
+                    ));
+                }
+                Err(error) => format!("PokeAPI is unreachable: {error}"),
+            };
+
+            if attempt == RETRIES {
+                return Err((StatusCode::BAD_GATEWAY, error));
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// `pokemon/{id}.json`, or whichever fixture has that name.
+fn find_fixture(directory: &Path, id_or_name: &str) -> Result<Pokemon, (StatusCode, String)> {
+    let directory = directory.join("pokemon");
+    let path = directory.join(format!("{id_or_name}.json"));
+    if path.is_file() {
+        return read_fixture(&path);
+    }
+    if id_or_name.parse::<u32>().is_err() {
+        for entry in std::fs::read_dir(&directory)
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let pokemon = read_fixture(&entry.path())?;
+            if pokemon.name == id_or_name {
+                return Ok(pokemon);
+            }
+        }
+    }
+    Err((StatusCode::NOT_FOUND, "No such Pokémon".to_string()))
+}
+
+fn read_fixture(path: &Path) -> Result<Pokemon, (StatusCode, String)> {
+    let text = std::fs::read_to_string(path).map_err(|error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unreadable fixture {}: {error}", path.display()),
+        )
+    })?;
+    serde_json::from_str(&text).map_err(|error| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid fixture {}: {error}", path.display()),
+        )
+    })
+}
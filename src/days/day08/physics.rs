@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+/// The height and gravity of the original challenge.
+const DEFAULT_HEIGHT: f64 = 10.0;
+const DEFAULT_GRAVITY: f64 = 9.825;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Planet {
+    Mercury,
+    Venus,
+    Earth,
+    Moon,
+    Mars,
+    Jupiter,
+    Saturn,
+    Uranus,
+    Neptune,
+    Pluto,
+}
+
+impl Planet {
+    /// Surface gravity, in m/s².
+    fn gravity(self) -> f64 {
+        match self {
+            Planet::Mercury => 3.7,
+            Planet::Venus => 8.87,
+            Planet::Earth => 9.80665,
+            Planet::Moon => 1.62,
+            Planet::Mars => 3.721,
+            Planet::Jupiter => 24.79,
+            Planet::Saturn => 10.44,
+            Planet::Uranus => 8.69,
+            Planet::Neptune => 11.15,
+            Planet::Pluto => 0.62,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+pub struct DropParams {
+    /// In m.
+    height: Option<f64>,
+    /// In m/s², or taken from the planet.
+    gravity: Option<f64>,
+    planet: Option<Planet>,
+    /// The `c` of a drag force `c·v²`, in kg/m, none by default.
+    drag: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Fall {
+    height: f64,
+    gravity: f64,
+    drag: f64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Impact {
+    /// In m/s.
+    pub velocity: f64,
+    /// In kg·m/s.
+    pub momentum: f64,
+    /// Kinetic energy, in J.
+    pub energy: f64,
+    /// Time to impact, in s.
+    pub time: f64,
+}
+
+impl DropParams {
+    pub fn fall(self) -> Result<Fall, String> {
+        let gravity = match (self.gravity, self.planet) {
+            (Some(_), Some(_)) => return Err("Give either the gravity or a planet".to_string()),
+            (Some(gravity), None) => gravity,
+            (None, Some(planet)) => planet.gravity(),
+            (None, None) => DEFAULT_GRAVITY,
+        };
+        let height = self.height.unwrap_or(DEFAULT_HEIGHT);
+        let drag = self.drag.unwrap_or(0.0);
+
+        if !height.is_finite() || height < 0.0 {
+            return Err("The height can't be negative".to_string());
+        }
+        if !gravity.is_finite() || gravity <= 0.0 {
+            return Err("The gravity must be positive".to_string());
+        }
+        if !drag.is_finite() || drag < 0.0 {
+            return Err("The drag can't be negative".to_string());
+        }
+        Ok(Fall {
+            height,
+            gravity,
+            drag,
+        })
+    }
+}
+
+impl Fall {
+    /// Dropping `mass` kg from rest.
+    pub fn impact(&self, mass: f64) -> Impact {
+        let Fall {
+            height,
+            gravity,
+            drag,
+        } = *self;
+
+        let (velocity, time) = if drag == 0.0 || mass == 0.0 {
+            // free fall, as a massless body isn't slowed down by anything either
+            let velocity = (gravity * height * 2.0).sqrt();
+            (velocity, (2.0 * height / gravity).sqrt())
+        } else {
+            // v = vt·tanh(g·t/vt) and h = vt²/g·ln(cosh(g·t/vt)), with vt the terminal velocity,
+            // solved for t with acosh(e^x) = x + ln(1 + sqrt(1 - e^-2x)) to not overflow
+            let terminal = (mass * gravity / drag).sqrt();
+            let x = gravity * height / (terminal * terminal);
+            let slowed = (1.0 - (-2.0 * x).exp()).sqrt();
+            (
+                terminal * slowed,
+                terminal / gravity * (x + (1.0 + slowed).ln()),
+            )
+        };
+
+        Impact {
+            velocity,
+            momentum: mass * velocity,
+            energy: mass * velocity * velocity / 2.0,
+            time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drag_slows_down() {
+        let free = DropParams::default().fall().unwrap().impact(6.0);
+        assert_eq!(free.momentum, 84.10707461325713);
+
+        let fall = DropParams {
+            drag: Some(0.5),
+            ..DropParams::default()
+        }
+        .fall()
+        .unwrap();
+        let slowed = fall.impact(6.0);
+        let terminal = (6.0 * DEFAULT_GRAVITY / 0.5_f64).sqrt();
+        assert!(slowed.velocity < free.velocity && slowed.velocity < terminal);
+        assert!(slowed.time > free.time);
+
+        // from far enough, the terminal velocity is reached
+        let fall = Fall {
+            height: 1e6,
+            ..fall
+        };
+        assert!((fall.impact(6.0).velocity - terminal).abs() < 1e-9);
+        assert!(fall.impact(6.0).time.is_finite());
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+const POUNDS_PER_KILOGRAM: f64 = 2.204_622_621_848_776;
+const FEET_PER_METRE: f64 = 3.280_839_895_013_123;
+
+/// The parts of a PokeAPI `pokemon` resource used here.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Pokemon {
+    pub id: u32,
+    pub name: String,
+    /// In decimetres.
+    pub height: u32,
+    /// In hectograms.
+    pub weight: u32,
+    pub types: Vec<TypeSlot>,
+    pub stats: Vec<BaseStat>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TypeSlot {
+    pub slot: u32,
+    #[serde(rename = "type")]
+    pub kind: Named,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BaseStat {
+    pub base_stat: u32,
+    pub stat: Named,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Named {
+    pub name: String,
+}
+
+impl Pokemon {
+    pub fn weight_in_kilo(&self) -> f64 {
+        self.weight as f64 / 10.0
+    }
+
+    pub fn height_in_metres(&self) -> f64 {
+        self.height as f64 / 10.0
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Measure {
+    value: f64,
+    unit: &'static str,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PokemonInfo {
+    id: u32,
+    name: String,
+    weight: Measure,
+    height: Measure,
+    /// By slot.
+    types: Vec<String>,
+    base_stats: BTreeMap<String, u32>,
+}
+
+impl PokemonInfo {
+    pub fn new(pokemon: &Pokemon, units: Units) -> PokemonInfo {
+        let (weight, height) = match units {
+            Units::Metric => (
+                Measure {
+                    value: pokemon.weight_in_kilo(),
+                    unit: "kg",
+                },
+                Measure {
+                    value: pokemon.height_in_metres(),
+                    unit: "m",
+                },
+            ),
+            Units::Imperial => (
+                Measure {
+                    value: round(pokemon.weight_in_kilo() * POUNDS_PER_KILOGRAM),
+                    unit: "lb",
+                },
+                Measure {
+                    value: round(pokemon.height_in_metres() * FEET_PER_METRE),
+                    unit: "ft",
+                },
+            ),
+        };
+
+        let mut types = pokemon.types.clone();
+        types.sort_by_key(|slot| slot.slot);
+
+        PokemonInfo {
+            id: pokemon.id,
+            name: pokemon.name.clone(),
+            weight,
+            height,
+            types: types.into_iter().map(|slot| slot.kind.name).collect(),
+            base_stats: pokemon
+                .stats
+                .iter()
+                .map(|stat| (stat.stat.name.clone(), stat.base_stat))
+                .collect(),
+        }
+    }
+}
+
+/// To the hundredth, what the conversions are given with.
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
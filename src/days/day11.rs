@@ -1,16 +1,30 @@
 use std::io::Cursor;
 
+use axum::body::Bytes;
 use axum::extract::Multipart;
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::post;
-use axum::Router;
-use image::{io::Reader as ImageReader, GenericImageView, Rgba};
+use axum::{Json, Router};
+use image::io::{Limits, Reader as ImageReader};
+use image::{DynamicImage, GenericImageView, ImageError, ImageFormat, Rgba};
 use tower_http::services::ServeDir;
 
+use analysis::{Analysis, Predicate, PredicateSpec, DEFAULT_COLORS, MAX_COLORS};
+use transform::{Pipeline, MAX_DIMENSION};
+
+mod analysis;
+mod transform;
+
+/// What decoding an image may take, on top of its dimensions.
+const MAX_DECODING_ALLOC: u64 = 256 * 1024 * 1024;
+
 pub fn get_day_11_router() -> Router {
     Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/red_pixels", post(red_pixels))
+        .route("/analyze", post(analyze))
+        .route("/transform", post(transform))
 }
 
 async fn red_pixels(mut multipart: Multipart) -> (StatusCode, String) {
@@ -33,6 +47,119 @@ async fn red_pixels(mut multipart: Multipart) -> (StatusCode, String) {
     (StatusCode::OK, res.to_string())
 }
 
+/// The image and its format, refusing those too large to be decoded safely with a 413.
+fn decode(data: &[u8]) -> Result<(DynamicImage, Option<ImageFormat>), (StatusCode, String)> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|error| (StatusCode::BAD_REQUEST, error.to_string()))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODING_ALLOC);
+    reader.limits(limits);
+
+    let format = reader.format();
+    match reader.decode() {
+        Ok(image) => Ok((image, format)),
+        Err(ImageError::Limits(_)) => Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Images can be at most {MAX_DIMENSION} × {MAX_DIMENSION} pixels"),
+        )),
+        Err(error) => Err((StatusCode::BAD_REQUEST, format!("Invalid image: {error}"))),
+    }
+}
+
+/// Decoding and processing images takes a while, keep it off the async workers.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, (StatusCode, String)> + Send + 'static,
+) -> Result<T, (StatusCode, String)> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|error| (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()))?
+}
+
+/// Takes the `image`, the `predicates` to count as a JSON list and the number of dominant
+/// `colors` wanted.
+async fn analyze(mut multipart: Multipart) -> Result<Json<Analysis>, (StatusCode, String)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, error);
+    let mut image: Option<Bytes> = None;
+    let mut predicates = Vec::new();
+    let mut colors = DEFAULT_COLORS;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|error| bad_request(error.body_text()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|error| bad_request(error.body_text()))?;
+        match name.as_str() {
+            "image" => image = Some(data),
+            "predicates" => {
+                let specs: Vec<PredicateSpec> = serde_json::from_slice(&data)
+                    .map_err(|error| bad_request(format!("Invalid predicates: {error}")))?;
+                predicates = Predicate::parse(specs).map_err(bad_request)?;
+            }
+            "colors" => {
+                colors = std::str::from_utf8(&data)
+                    .ok()
+                    .and_then(|colors| colors.trim().parse().ok())
+                    .filter(|colors| *colors <= MAX_COLORS)
+                    .ok_or(bad_request(format!(
+                        "colors must be a number up to {MAX_COLORS}"
+                    )))?;
+            }
+            _ => {}
+        }
+    }
+
+    let data = image.ok_or(bad_request("Missing image".to_string()))?;
+    let analysis = blocking(move || {
+        let (image, _) = decode(&data)?;
+        Ok(analysis::analyze(&image, &predicates, colors))
+    })
+    .await?;
+    Ok(Json(analysis))
+}
+
+/// Takes the `image` and the JSON `pipeline` to run on it.
+async fn transform(mut multipart: Multipart) -> Result<Response, (StatusCode, String)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, error);
+    let mut image: Option<Bytes> = None;
+    let mut pipeline = Pipeline::default();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|error| bad_request(error.body_text()))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|error| bad_request(error.body_text()))?;
+        match name.as_str() {
+            "image" => image = Some(data),
+            "pipeline" => {
+                pipeline = serde_json::from_slice(&data)
+                    .map_err(|error| bad_request(format!("Invalid pipeline: {error}")))?;
+            }
+            _ => {}
+        }
+    }
+
+    let data = image.ok_or(bad_request("Missing image".to_string()))?;
+    let (bytes, format) = blocking(move || {
+        let (image, input) = decode(&data)?;
+        pipeline.run(image, input).map_err(bad_request)
+    })
+    .await?;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], bytes).into_response())
+}
+
 fn is_magical_red() -> fn(&(u32, u32, Rgba<u8>)) -> bool {
     |(_x, _y, Rgba([r, g, b, _a]))| *r as u16 > (*g as u16 + *b as u16)
 }
@@ -42,9 +169,19 @@ mod tests {
     use axum::http::StatusCode;
     use axum_test::multipart::{MultipartForm, Part};
     use axum_test::TestServer;
+    use image::{ImageOutputFormat, RgbaImage};
+    use serde_json::json;
 
     use super::*;
 
+    fn png(image: RgbaImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            .unwrap();
+        png
+    }
+
     #[tokio::test]
     async fn task1() {
         let app = get_day_11_router();
@@ -91,4 +228,155 @@ mod tests {
 
         response.assert_text(73034.to_string());
     }
+
+    #[tokio::test]
+    async fn analyze_image() {
+        let app = get_day_11_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Two red pixels, a blue and a white one.
+        let pixels = [
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [255; 4],
+        ];
+        let png = png(RgbaImage::from_fn(2, 2, |x, y| {
+            Rgba(pixels[(2 * y + x) as usize])
+        }));
+
+        // Send the request.
+        let predicates = json!([
+            "red",
+            {"name": "blues", "hsv": {"hue": [200, 260], "saturation": [0.5, 1]}},
+            {"name": "pale", "hsv": {"saturation": [0, 0.1]}},
+            {"color": "pink", "tolerance": 100}
+        ]);
+        let multipart = MultipartForm::new()
+            .add_part("image", Part::bytes(png).file_name("tiny.png"))
+            .add_text("predicates", predicates.to_string())
+            .add_text("colors", "3");
+        let response = server.post("/analyze").multipart(multipart).await;
+
+        response.assert_status(StatusCode::OK);
+        let analysis = response.json::<serde_json::Value>();
+        assert_eq!(analysis["width"], 2);
+        assert_eq!(analysis["histograms"]["red"][255], 3);
+        assert_eq!(analysis["histograms"]["blue"][255], 2);
+        assert_eq!(
+            analysis["counts"],
+            json!({"red": 2, "blues": 1, "pale": 1, "pink": 1})
+        );
+        // (2·54.213 + 18.411 + 255) / 4
+        assert!((analysis["brightness"].as_f64().unwrap() - 95.4593).abs() < 1e-3);
+        assert_eq!(
+            analysis["dominant_colors"][0],
+            json!({"color": "#ff0000", "rgb": [255, 0, 0], "share": 0.5})
+        );
+        assert_eq!(analysis["dominant_colors"].as_array().unwrap().len(), 3);
+
+        // Send the request.
+        let multipart = MultipartForm::new()
+            .add_part(
+                "image",
+                Part::bytes(include_bytes!("../../assets/decoration.png").as_slice()),
+            )
+            .add_text("predicates", r#"["chartreuse"]"#);
+        let response = server.post("/analyze").multipart(multipart).await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("Unknown color 'chartreuse'");
+    }
+
+    #[tokio::test]
+    async fn transform_image() {
+        let app = get_day_11_router();
+
+        // Run the application for testing.
+        let server = TestServer::new(app).unwrap();
+
+        // Red on the left half, blue on the right.
+        let image = png(RgbaImage::from_fn(40, 20, |x, _| {
+            Rgba(if x < 20 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            })
+        }));
+
+        // Send the request.
+        let pipeline = json!({
+            "operations": [
+                {"crop": {"x": 10, "y": 0, "width": 20, "height": 20}},
+                {"rotate": 90},
+                {"resize": {"width": 10}},
+                {"blur": 0.5}
+            ],
+            "format": "jpeg",
+            "quality": 90
+        });
+        let multipart = MultipartForm::new()
+            .add_part("image", Part::bytes(image.clone()).file_name("flag.png"))
+            .add_text("pipeline", pipeline.to_string());
+        let response = server.post("/transform").multipart(multipart).await;
+
+        response.assert_status(StatusCode::OK);
+        assert_eq!(response.header(header::CONTENT_TYPE), "image/jpeg");
+        let transformed = image::load_from_memory(response.as_bytes()).unwrap();
+        assert_eq!(transformed.dimensions(), (10, 10));
+        // rotated clockwise, so red is now on top
+        let Rgba([r, _, b, _]) = transformed.get_pixel(5, 1);
+        assert!(r > 200 && b < 50);
+
+        for (format, content_type) in [("webp", "image/webp"), ("gif", "image/gif")] {
+            // Send the request.
+            let pipeline = json!({"operations": ["grayscale"], "format": format});
+            let multipart = MultipartForm::new()
+                .add_part("image", Part::bytes(image.clone()))
+                .add_text("pipeline", pipeline.to_string());
+            let response = server.post("/transform").multipart(multipart).await;
+
+            response.assert_status(StatusCode::OK);
+            assert_eq!(response.header(header::CONTENT_TYPE), content_type);
+            let transformed = image::load_from_memory(response.as_bytes()).unwrap();
+            assert_eq!(transformed.dimensions(), (40, 20));
+        }
+
+        // Without a pipeline, the image comes back in its format.
+        // Send the request.
+        let multipart = MultipartForm::new().add_part("image", Part::bytes(image.clone()));
+        let response = server.post("/transform").multipart(multipart).await;
+
+        response.assert_status(StatusCode::OK);
+        assert_eq!(response.header(header::CONTENT_TYPE), "image/png");
+
+        // Send the request.
+        let pipeline = json!({"operations": [{"resize": {"width": 40000}}]});
+        let multipart = MultipartForm::new()
+            .add_part("image", Part::bytes(image.clone()))
+            .add_text("pipeline", pipeline.to_string());
+        let response = server.post("/transform").multipart(multipart).await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let pipeline =
+            json!({"operations": [{"crop": {"x": 30, "y": 0, "width": 20, "height": 20}}]});
+        let multipart = MultipartForm::new()
+            .add_part("image", Part::bytes(image))
+            .add_text("pipeline", pipeline.to_string());
+        let response = server.post("/transform").multipart(multipart).await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+        response.assert_text("Can't crop 20 × 20 at (30, 0) out of 40 × 20");
+
+        // Send the request.
+        let wide = png(RgbaImage::new(MAX_DIMENSION + 1, 1));
+        let multipart = MultipartForm::new().add_part("image", Part::bytes(wide));
+        let response = server.post("/transform").multipart(multipart).await;
+
+        response.assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+
+use image::{DynamicImage, Rgba};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_COLORS: usize = 5;
+pub const MAX_COLORS: usize = 16;
+const MAX_PREDICATES: usize = 100;
+/// Dominant colors are clustered from at most this many pixels.
+const MAX_SAMPLES: usize = 10_000;
+const MAX_ITERATIONS: usize = 20;
+/// How far in RGB a pixel can be from a named color by default.
+const DEFAULT_TOLERANCE: f64 = 64.0;
+
+const NAMED_COLORS: [(&str, [u8; 3]); 16] = [
+    ("black", [0, 0, 0]),
+    ("white", [255, 255, 255]),
+    ("gray", [128, 128, 128]),
+    ("silver", [192, 192, 192]),
+    ("red", [255, 0, 0]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("blue", [0, 0, 255]),
+    ("yellow", [255, 255, 0]),
+    ("cyan", [0, 255, 255]),
+    ("magenta", [255, 0, 255]),
+    ("orange", [255, 165, 0]),
+    ("purple", [128, 0, 128]),
+    ("pink", [255, 192, 203]),
+    ("brown", [165, 42, 42]),
+    ("gold", [255, 215, 0]),
+];
+
+fn named_color(name: &str) -> Option<[u8; 3]> {
+    NAMED_COLORS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(_, rgb)| *rgb)
+}
+
+/// Bounds of each HSV component, hue in degrees wrapping around when `from` is past `to`,
+/// saturation and value from 0 to 1.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct HsvRange {
+    #[serde(default = "full_hue")]
+    hue: (f64, f64),
+    #[serde(default = "full_range")]
+    saturation: (f64, f64),
+    #[serde(default = "full_range")]
+    value: (f64, f64),
+}
+
+fn full_hue() -> (f64, f64) {
+    (0.0, 360.0)
+}
+
+fn full_range() -> (f64, f64) {
+    (0.0, 1.0)
+}
+
+/// A named color on its own, or a named predicate on HSV or on the distance to a color.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum PredicateSpec {
+    Color(String),
+    Detailed {
+        name: Option<String>,
+        hsv: Option<HsvRange>,
+        color: Option<String>,
+        tolerance: Option<f64>,
+    },
+}
+
+enum Test {
+    Hsv(HsvRange),
+    Near { rgb: [u8; 3], tolerance: f64 },
+}
+
+pub struct Predicate {
+    name: String,
+    test: Test,
+}
+
+impl Predicate {
+    pub fn parse(specs: Vec<PredicateSpec>) -> Result<Vec<Predicate>, String> {
+        if specs.len() > MAX_PREDICATES {
+            return Err(format!("At most {MAX_PREDICATES} predicates are counted"));
+        }
+        specs.into_iter().map(Predicate::new).collect()
+    }
+
+    fn new(spec: PredicateSpec) -> Result<Predicate, String> {
+        let near = |color: &str, tolerance: Option<f64>| {
+            let rgb = named_color(color).ok_or(format!("Unknown color '{color}'"))?;
+            let tolerance = tolerance.unwrap_or(DEFAULT_TOLERANCE);
+            if !tolerance.is_finite() || tolerance < 0.0 {
+                return Err(format!("Invalid tolerance for '{color}'"));
+            }
+            Ok(Test::Near { rgb, tolerance })
+        };
+
+        match spec {
+            PredicateSpec::Color(color) => Ok(Predicate {
+                test: near(&color, None)?,
+                name: color,
+            }),
+            PredicateSpec::Detailed {
+                name,
+                hsv,
+                color,
+                tolerance,
+            } => {
+                let (test, default_name) = match (hsv, color) {
+                    (Some(range), None) => (Test::Hsv(range.validate()?), None),
+                    (None, Some(color)) => (near(&color, tolerance)?, Some(color)),
+                    _ => return Err("A predicate takes either an HSV range or a color".to_string()),
+                };
+                let name = name
+                    .or(default_name)
+                    .ok_or("HSV predicates need a name".to_string())?;
+                Ok(Predicate { name, test })
+            }
+        }
+    }
+
+    fn matches(&self, Rgba([r, g, b, _]): Rgba<u8>) -> bool {
+        match &self.test {
+            Test::Hsv(range) => range.contains(hsv(r, g, b)),
+            Test::Near { rgb, tolerance } => {
+                let distance = [r, g, b]
+                    .iter()
+                    .zip(rgb)
+                    .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                distance <= *tolerance
+            }
+        }
+    }
+}
+
+impl HsvRange {
+    fn validate(self) -> Result<HsvRange, String> {
+        let (h0, h1) = self.hue;
+        let valid = [h0, h1].iter().all(|h| (0.0..=360.0).contains(h))
+            && [self.saturation, self.value]
+                .iter()
+                .all(|(from, to)| 0.0 <= *from && from <= to && *to <= 1.0);
+        if valid {
+            Ok(self)
+        } else {
+            Err("Hue goes from 0 to 360, saturation and value from 0 to 1".to_string())
+        }
+    }
+
+    fn contains(&self, (h, s, v): (f64, f64, f64)) -> bool {
+        let (h0, h1) = self.hue;
+        let hue = if h0 <= h1 {
+            h0 <= h && h <= h1
+        } else {
+            h0 <= h || h <= h1
+        };
+        hue && self.saturation.0 <= s
+            && s <= self.saturation.1
+            && self.value.0 <= v
+            && v <= self.value.1
+    }
+}
+
+/// Hue in degrees, saturation and value from 0 to 1.
+fn hsv(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let [r, g, b] = [r, g, b].map(|c| c as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+#[derive(Serialize, Debug)]
+pub struct Histograms {
+    red: Vec<u64>,
+    green: Vec<u64>,
+    blue: Vec<u64>,
+    alpha: Vec<u64>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DominantColor {
+    /// As `#rrggbb`.
+    color: String,
+    rgb: [u8; 3],
+    /// Of the opaque pixels.
+    share: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Analysis {
+    width: u32,
+    height: u32,
+    histograms: Histograms,
+    /// Mean luma, from 0 to 255.
+    brightness: f64,
+    dominant_colors: Vec<DominantColor>,
+    counts: BTreeMap<String, u64>,
+}
+
+pub fn analyze(image: &DynamicImage, predicates: &[Predicate], colors: usize) -> Analysis {
+    let image = image.to_rgba8();
+    let mut histograms = Histograms {
+        red: vec![0; 256],
+        green: vec![0; 256],
+        blue: vec![0; 256],
+        alpha: vec![0; 256],
+    };
+    let mut luma = 0.0;
+    let mut counts = vec![0; predicates.len()];
+
+    for &pixel in image.pixels() {
+        let Rgba([r, g, b, a]) = pixel;
+        histograms.red[r as usize] += 1;
+        histograms.green[g as usize] += 1;
+        histograms.blue[b as usize] += 1;
+        histograms.alpha[a as usize] += 1;
+        luma += 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+        for (count, predicate) in counts.iter_mut().zip(predicates) {
+            if predicate.matches(pixel) {
+                *count += 1;
+            }
+        }
+    }
+
+    let pixels = image.width() as f64 * image.height() as f64;
+    let opaque: Vec<[f64; 3]> = image
+        .pixels()
+        .filter(|Rgba([_, _, _, a])| *a > 0)
+        .map(|Rgba([r, g, b, _])| [*r as f64, *g as f64, *b as f64])
+        .collect();
+
+    Analysis {
+        width: image.width(),
+        height: image.height(),
+        histograms,
+        brightness: if pixels == 0.0 { 0.0 } else { luma / pixels },
+        dominant_colors: dominant_colors(&opaque, colors),
+        counts: predicates
+            .iter()
+            .map(|predicate| predicate.name.clone())
+            .zip(counts)
+            .collect(),
+    }
+}
+
+fn distance([r0, g0, b0]: &[f64; 3], [r1, g1, b1]: &[f64; 3]) -> f64 {
+    (r0 - r1).powi(2) + (g0 - g1).powi(2) + (b0 - b1).powi(2)
+}
+
+/// K-means over an even sample of the pixels, started from centroids spread by brightness
+/// so the same image always gives the same colors.
+fn dominant_colors(pixels: &[[f64; 3]], k: usize) -> Vec<DominantColor> {
+    let step = pixels.len().div_ceil(MAX_SAMPLES).max(1);
+    let mut sample: Vec<[f64; 3]> = pixels.iter().step_by(step).copied().collect();
+    if sample.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    sample.sort_by(|a, b| a.iter().sum::<f64>().total_cmp(&b.iter().sum::<f64>()));
+
+    let k = k.min(sample.len());
+    let mut centroids: Vec<[f64; 3]> = (0..k)
+        .map(|i| sample[(2 * i + 1) * sample.len() / (2 * k)])
+        .collect();
+    let mut assignments = vec![0; sample.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (pixel, assigned) in sample.iter().zip(assignments.iter_mut()) {
+            let nearest = (0..centroids.len())
+                .min_by(|&a, &b| {
+                    distance(pixel, &centroids[a]).total_cmp(&distance(pixel, &centroids[b]))
+                })
+                .unwrap();
+            if nearest != *assigned {
+                *assigned = nearest;
+                changed = true;
+            }
+        }
+
+        let mut sums = vec![([0.0; 3], 0usize); centroids.len()];
+        for (pixel, &assigned) in sample.iter().zip(&assignments) {
+            let (sum, count) = &mut sums[assigned];
+            for (total, channel) in sum.iter_mut().zip(pixel) {
+                *total += channel;
+            }
+            *count += 1;
+        }
+        for (centroid, (sum, count)) in centroids.iter_mut().zip(sums) {
+            if count > 0 {
+                *centroid = sum.map(|total| total / count as f64);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut sizes = vec![0usize; centroids.len()];
+    for &assigned in &assignments {
+        sizes[assigned] += 1;
+    }
+    let mut colors: Vec<DominantColor> = centroids
+        .iter()
+        .zip(sizes)
+        .filter(|(_, size)| *size > 0)
+        .map(|(centroid, size)| {
+            let rgb = centroid.map(|channel| channel.round() as u8);
+            DominantColor {
+                color: format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]),
+                rgb,
+                share: size as f64 / sample.len() as f64,
+            }
+        })
+        .collect();
+    colors.sort_by(|a, b| b.share.total_cmp(&a.share));
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsv_conversion() {
+        assert_eq!(hsv(255, 0, 0), (0.0, 1.0, 1.0));
+        assert_eq!(hsv(0, 0, 255), (240.0, 1.0, 1.0));
+        assert_eq!(hsv(255, 0, 255), (300.0, 1.0, 1.0));
+        assert_eq!(hsv(51, 51, 51), (0.0, 0.0, 0.2));
+
+        // reds on both sides of 0°
+        let reds = HsvRange {
+            hue: (330.0, 30.0),
+            saturation: full_range(),
+            value: full_range(),
+        };
+        assert!(reds.contains(hsv(255, 0, 64)));
+        assert!(reds.contains(hsv(255, 64, 0)));
+        assert!(!reds.contains(hsv(0, 255, 0)));
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::Cursor;
+
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ColorType, DynamicImage, ImageFormat, ImageOutputFormat};
+use serde::Deserialize;
+
+/// Widest and highest image decoded or produced, so a small upload can't blow up in memory.
+pub const MAX_DIMENSION: u32 = 4096;
+const MAX_OPERATIONS: usize = 20;
+const MAX_BLUR: f32 = 50.0;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Webp,
+    Gif,
+}
+
+impl OutputFormat {
+    fn of(format: ImageFormat) -> Option<OutputFormat> {
+        match format {
+            ImageFormat::Png => Some(OutputFormat::Png),
+            ImageFormat::Jpeg => Some(OutputFormat::Jpeg),
+            ImageFormat::WebP => Some(OutputFormat::Webp),
+            ImageFormat::Gif => Some(OutputFormat::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+            OutputFormat::Webp => ImageFormat::WebP,
+            OutputFormat::Gif => ImageFormat::Gif,
+        }
+        .to_mime_type()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Within `width` × `height`, keeping the aspect ratio unless `exact`.
+    /// A missing dimension follows the other one.
+    Resize {
+        width: Option<u32>,
+        height: Option<u32>,
+        #[serde(default)]
+        exact: bool,
+    },
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Clockwise, by a multiple of 90 degrees.
+    Rotate(u32),
+    Grayscale,
+    /// The standard deviation of the gaussian, in pixels.
+    Blur(f32),
+}
+
+/// The operations are applied in order, the image is then written in `format`,
+/// or in the format it came in when that's one of the supported ones.
+#[derive(Deserialize, Debug, Default)]
+pub struct Pipeline {
+    #[serde(default)]
+    operations: Vec<Operation>,
+    format: Option<OutputFormat>,
+    /// For JPEG, WebP is always lossless.
+    quality: Option<u8>,
+}
+
+impl Operation {
+    fn apply(&self, image: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(match *self {
+            Operation::Resize {
+                width,
+                height,
+                exact,
+            } => {
+                let (w, h) = (image.width() as f64, image.height() as f64);
+                let (width, height) = match (width, height) {
+                    (Some(width), Some(height)) => (width, height),
+                    (Some(width), None) => (width, (h * width as f64 / w).round().max(1.0) as u32),
+                    (None, Some(height)) => {
+                        ((w * height as f64 / h).round().max(1.0) as u32, height)
+                    }
+                    (None, None) => return Err("Resize to which width or height?".to_string()),
+                };
+                if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+                    return Err(format!(
+                        "Images are resized to at most {MAX_DIMENSION} × {MAX_DIMENSION} pixels"
+                    ));
+                }
+                if exact {
+                    image.resize_exact(width, height, FilterType::Lanczos3)
+                } else {
+                    image.resize(width, height, FilterType::Lanczos3)
+                }
+            }
+            Operation::Crop {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let inside = width > 0
+                    && height > 0
+                    && x.checked_add(width).is_some_and(|end| end <= image.width())
+                    && y.checked_add(height)
+                        .is_some_and(|end| end <= image.height());
+                if !inside {
+                    return Err(format!(
+                        "Can't crop {width} × {height} at ({x}, {y}) out of {} × {}",
+                        image.width(),
+                        image.height()
+                    ));
+                }
+                image.crop_imm(x, y, width, height)
+            }
+            Operation::Rotate(degrees) => match degrees % 360 {
+                0 => image,
+                90 => image.rotate90(),
+                180 => image.rotate180(),
+                270 => image.rotate270(),
+                _ => return Err("Images are rotated by multiples of 90 degrees".to_string()),
+            },
+            Operation::Grayscale => image.grayscale(),
+            Operation::Blur(sigma) => {
+                if !(sigma > 0.0 && sigma <= MAX_BLUR) {
+                    return Err(format!("The blur goes from 0 to {MAX_BLUR}"));
+                }
+                image.blur(sigma)
+            }
+        })
+    }
+}
+
+impl Pipeline {
+    /// The transformed image, and the format it was written in.
+    pub fn run(
+        &self,
+        image: DynamicImage,
+        input: Option<ImageFormat>,
+    ) -> Result<(Vec<u8>, OutputFormat), String> {
+        if self.operations.len() > MAX_OPERATIONS {
+            return Err(format!("At most {MAX_OPERATIONS} operations are applied"));
+        }
+        if self
+            .quality
+            .is_some_and(|quality| !(1..=100).contains(&quality))
+        {
+            return Err("The quality goes from 1 to 100".to_string());
+        }
+
+        let image = self
+            .operations
+            .iter()
+            .try_fold(image, |image, operation| operation.apply(image))?;
+        let format = self
+            .format
+            .or(input.and_then(OutputFormat::of))
+            .unwrap_or(OutputFormat::Png);
+
+        let mut bytes = Cursor::new(Vec::new());
+        let written = match format {
+            OutputFormat::Png => image.write_to(&mut bytes, ImageOutputFormat::Png),
+            // JPEG has no transparency, and the GIF encoder only takes RGBA
+            OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()).write_to(
+                &mut bytes,
+                ImageOutputFormat::Jpeg(self.quality.unwrap_or(75)),
+            ),
+            OutputFormat::Gif => DynamicImage::ImageRgba8(image.to_rgba8())
+                .write_to(&mut bytes, ImageOutputFormat::Gif),
+            OutputFormat::Webp => {
+                let rgba = image.to_rgba8();
+                WebPEncoder::new_lossless(&mut bytes).encode(
+                    &rgba,
+                    rgba.width(),
+                    rgba.height(),
+                    ColorType::Rgba8,
+                )
+            }
+        };
+        written.map_err(|error| format!("Can't write the image: {error}"))?;
+
+        Ok((bytes.into_inner(), format))
+    }
+}
@@ -43,7 +43,7 @@ async fn nice(Json(input): Json<Input>) -> (StatusCode, Json<Result>) {
 
 fn is_nice(input: &str) -> bool {
     const VOWELS: &str = "aeiouy";
-    const BAD_SUBSTRINGS: [&[u8]; 4] = [&[b'a', b'b'], &[b'c', b'd'], &[b'p', b'q'], &[b'x', b'y']];
+    const BAD_SUBSTRINGS: [&[u8]; 4] = [b"ab", b"cd", b"pq", b"xy"];
 
     // Must contain at least three vowels (aeiouy),
     // at least one letter that appears twice in a row,
@@ -71,17 +71,16 @@ async fn process_ping_message(
             "serve" => {
                 started.store(true, Ordering::Relaxed);
             }
-            "ping" => {
+            "ping"
                 if started.load(Ordering::Relaxed)
                     && sender
                         .write()
                         .await
                         .send(Message::Text(String::from("pong")))
                         .await
-                        .is_err()
-                {
-                    return ControlFlow::Break(());
-                }
+                        .is_err() =>
+            {
+                return ControlFlow::Break(());
             }
             _ => {}
         }
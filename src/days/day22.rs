@@ -36,7 +36,7 @@ async fn rocket(payload: String) -> impl IntoResponse {
 
     let path = bfs(
         &0,
-        |node| portals[&node].clone(),
+        |node| portals[node].clone(),
         |&node| node == (stars.len() as i32 - 1),
     )
     .unwrap();
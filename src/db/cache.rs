@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::methods;
+use crate::db::structs::MyState;
+
+/// Results of the aggregate queries, dropped as a whole on every mutation.
+#[derive(Clone, Default)]
+pub struct QueryCache {
+    inner: Arc<RwLock<CacheEntries>>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    generation: u64,
+    entries: HashMap<String, Value>,
+}
+
+impl QueryCache {
+    pub fn invalidate(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.generation += 1;
+        inner.entries.clear();
+    }
+
+    async fn get_or_load<T, F, Fut>(&self, key: String, load: F) -> T
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let generation = {
+            let inner = self.inner.read().unwrap();
+            if let Some(value) = inner.entries.get(&key) {
+                return serde_json::from_value(value.clone()).unwrap();
+            }
+            inner.generation
+        };
+
+        let value = load().await;
+
+        // a mutation during the query makes its result stale, don't keep it
+        let mut inner = self.inner.write().unwrap();
+        if inner.generation == generation {
+            inner
+                .entries
+                .insert(key, serde_json::to_value(&value).unwrap());
+        }
+        value
+    }
+}
+
+pub async fn get_number_order(db: MyState) -> i64 {
+    db.cache
+        .clone()
+        .get_or_load("total".to_string(), || methods::get_number_order(db))
+        .await
+}
+
+pub async fn get_most_popular_order(db: MyState) -> Value {
+    db.cache
+        .clone()
+        .get_or_load("popular".to_string(), || {
+            methods::get_most_popular_order(db)
+        })
+        .await
+}
+
+pub async fn get_number_region(
+    db: MyState,
+    rollup: bool,
+    depth: Option<i32>,
+) -> Vec<(String, i64)> {
+    db.cache
+        .clone()
+        .get_or_load(format!("regions/{rollup}/{depth:?}"), || {
+            methods::get_number_region(db, rollup, depth)
+        })
+        .await
+}
+
+pub async fn get_top_gifts(
+    db: MyState,
+    nb_gifts: i32,
+    rollup: bool,
+    depth: Option<i32>,
+) -> Vec<(String, Option<String>)> {
+    db.cache
+        .clone()
+        .get_or_load(format!("top_gifts/{nb_gifts}/{rollup}/{depth:?}"), || {
+            methods::get_top_gifts(db, nb_gifts, rollup, depth)
+        })
+        .await
+}
@@ -1,7 +1,21 @@
-use serde_json::Value;
+use serde_json::{Map, Value};
+use sqlx::types::Json;
 use sqlx::Row;
 
-use crate::db::structs::{MyState, Order, Region};
+use crate::db::structs::{ContestEntry, ContestPlacing, MyState, Order, Region, StoredReindeer};
+
+/// Channel on which every mutation of the orders or regions is announced,
+/// the payload is the schema holding the tables.
+pub const ORDERS_CHANNEL: &str = "orders_changed";
+
+async fn notify_orders_changed(db: &MyState) {
+    db.cache.invalidate();
+    sqlx::query("SELECT pg_notify($1, current_schema())")
+        .bind(ORDERS_CHANNEL)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+}
 
 pub async fn reset(db: MyState) {
     sqlx::query(include_str!("../../migrations/1_drop_orders.sql"))
@@ -20,9 +34,16 @@ pub async fn reset(db: MyState) {
         .execute(&db.pool)
         .await
         .unwrap();
+    sqlx::query(include_str!("../../migrations/5_add_regions_parent.sql"))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    notify_orders_changed(&db).await;
 }
 
-pub async fn insert_orders(db: MyState, data: Vec<Order>) {
+/// All the orders or none of them.
+pub async fn insert_orders(db: MyState, data: Vec<Order>) -> Result<(), sqlx::Error> {
+    let mut tx = db.pool.begin().await?;
     for order in data {
         sqlx::query(
             "INSERT INTO orders (id, region_id, gift_name, quantity) VALUES ($1, $2, $3, $4)",
@@ -31,10 +52,12 @@ pub async fn insert_orders(db: MyState, data: Vec<Order>) {
         .bind(order.region_id)
         .bind(order.gift_name)
         .bind(order.quantity)
-        .execute(&db.pool)
-        .await
-        .unwrap();
+        .execute(&mut *tx)
+        .await?;
     }
+    tx.commit().await?;
+    notify_orders_changed(&db).await;
+    Ok(())
 }
 
 pub async fn get_number_order(db: MyState) -> i64 {
@@ -42,7 +65,7 @@ pub async fn get_number_order(db: MyState) -> i64 {
         .fetch_one(&db.pool)
         .await
     {
-        Ok(row) => row.get::<i64, _>("sum"),
+        Ok(row) => row.get::<Option<i64>, _>("sum").unwrap_or(0),
         Err(_) => 0,
     }
 }
@@ -57,45 +80,94 @@ pub async fn get_most_popular_order(db: MyState) -> Value {
     }
 }
 
-pub async fn insert_regions(db: MyState, data: Vec<Region>) {
+/// All the regions or none of them.
+pub async fn insert_regions(db: MyState, data: Vec<Region>) -> Result<(), sqlx::Error> {
+    let mut tx = db.pool.begin().await?;
     for region in data {
-        sqlx::query("INSERT INTO regions (id, name) VALUES ($1, $2)")
+        sqlx::query("INSERT INTO regions (id, name, parent_id) VALUES ($1, $2, $3)")
             .bind(region.id)
             .bind(region.name)
-            .execute(&db.pool)
-            .await
-            .unwrap();
+            .bind(region.parent_id)
+            .execute(&mut *tx)
+            .await?;
     }
+    tx.commit().await?;
+    notify_orders_changed(&db).await;
+    Ok(())
 }
 
-pub async fn get_number_region(db: MyState) -> Vec<(String, i64)> {
-    sqlx::query_as(
-        "SELECT r.name AS region, SUM(o.quantity)
+// `depths` gives the level of every region reachable from a root (0 for roots),
+// `closure` pairs every region with itself and all of its descendants.
+// The path array stops the recursion if the parent links ever form a cycle.
+const REGION_TREE_CTE: &str = "WITH RECURSIVE depths AS (
+    SELECT id, 0 AS depth, ARRAY[id] AS path
+    FROM regions
+    WHERE parent_id IS NULL
+    UNION ALL
+    SELECT r.id, d.depth + 1, d.path || r.id
+    FROM regions r
+    JOIN depths d ON r.parent_id = d.id
+    WHERE NOT r.id = ANY(d.path)
+),
+closure AS (
+    SELECT id AS ancestor_id, id AS descendant_id, ARRAY[id] AS path
+    FROM regions
+    UNION ALL
+    SELECT c.ancestor_id, r.id, c.path || r.id
+    FROM closure c
+    JOIN regions r ON r.parent_id = c.descendant_id
+    WHERE NOT r.id = ANY(c.path)
+)";
+
+/// Totals per region. With `rollup` the orders of every descendant are added to
+/// the region's own, and `depth` restricts the output to the regions at that level.
+pub async fn get_number_region(
+    db: MyState,
+    rollup: bool,
+    depth: Option<i32>,
+) -> Vec<(String, i64)> {
+    sqlx::query_as(&format!(
+        "{REGION_TREE_CTE}
+SELECT r.name AS region, SUM(o.quantity)
 FROM regions r
-LEFT JOIN orders o ON r.id = o.region_id
+JOIN closure c ON c.ancestor_id = r.id AND ($1 OR c.ancestor_id = c.descendant_id)
+LEFT JOIN orders o ON c.descendant_id = o.region_id
+WHERE $2::INT IS NULL OR r.id IN (SELECT id FROM depths WHERE depth = $2)
 GROUP BY r.name
 HAVING SUM(o.quantity) IS NOT NULL
-ORDER BY r.name;",
-    )
+ORDER BY r.name;"
+    ))
+    .bind(rollup)
+    .bind(depth)
     .fetch_all(&db.pool)
     .await
     .unwrap()
 }
 
-pub async fn get_top_gifts(db: MyState, nb_gifts: i32) -> Vec<(String, Option<String>)> {
+pub async fn get_top_gifts(
+    db: MyState,
+    nb_gifts: i32,
+    rollup: bool,
+    depth: Option<i32>,
+) -> Vec<(String, Option<String>)> {
     if nb_gifts == 0 {
-        return get_no_top_gifts(&db).await;
+        return get_no_top_gifts(&db, depth).await;
     }
-    sqlx::query_as(
-        "WITH RankedGifts AS (
+    sqlx::query_as(&format!(
+        "{REGION_TREE_CTE},
+RankedGifts AS (
     SELECT
         r.name AS region,
         o.gift_name,
         ROW_NUMBER() OVER (PARTITION BY r.id ORDER BY -SUM(o.quantity), o.gift_name) AS row_num
     FROM
         regions r
+            JOIN
+        closure c ON c.ancestor_id = r.id AND ($2 OR c.ancestor_id = c.descendant_id)
             LEFT JOIN
-        orders o ON r.id = o.region_id
+        orders o ON c.descendant_id = o.region_id
+    WHERE
+        $3::INT IS NULL OR r.id IN (SELECT id FROM depths WHERE depth = $3)
     GROUP BY
         r.name, o.gift_name, r.id
 )
@@ -110,17 +182,221 @@ GROUP BY
     region
 ORDER BY
     region;
-",
-    )
+"
+    ))
     .bind(nb_gifts)
+    .bind(rollup)
+    .bind(depth)
     .fetch_all(&db.pool)
     .await
     .unwrap()
 }
 
-async fn get_no_top_gifts(db: &MyState) -> Vec<(String, Option<String>)> {
-    sqlx::query_as("SELECT name, null FROM regions ORDER BY name")
+async fn get_no_top_gifts(db: &MyState, depth: Option<i32>) -> Vec<(String, Option<String>)> {
+    sqlx::query_as(&format!(
+        "{REGION_TREE_CTE}
+SELECT name, null
+FROM regions
+WHERE $1::INT IS NULL OR id IN (SELECT id FROM depths WHERE depth = $1)
+ORDER BY name"
+    ))
+    .bind(depth)
+    .fetch_all(&db.pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_regions(db: MyState) -> Vec<Region> {
+    sqlx::query_as("SELECT id, name, parent_id FROM regions ORDER BY name")
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+}
+
+pub async fn region_exists(db: MyState, id: i32) -> bool {
+    sqlx::query("SELECT 1 FROM regions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db.pool)
+        .await
+        .unwrap()
+        .is_some()
+}
+
+/// Ids of the region and all of its descendants.
+pub async fn get_region_subtree(db: MyState, id: i32) -> Vec<i32> {
+    sqlx::query_scalar(&format!(
+        "{REGION_TREE_CTE}
+SELECT descendant_id FROM closure WHERE ancestor_id = $1"
+    ))
+    .bind(id)
+    .fetch_all(&db.pool)
+    .await
+    .unwrap()
+}
+
+pub async fn update_region_parent(db: MyState, id: i32, parent_id: Option<i32>) -> bool {
+    let updated = sqlx::query("UPDATE regions SET parent_id = $2 WHERE id = $1")
+        .bind(id)
+        .bind(parent_id)
+        .execute(&db.pool)
+        .await
+        .unwrap()
+        .rows_affected()
+        > 0;
+    notify_orders_changed(&db).await;
+    updated
+}
+
+/// Removes a region, its children are attached to its own parent.
+pub async fn delete_region(db: MyState, id: i32) -> bool {
+    let mut tx = db.pool.begin().await.unwrap();
+    sqlx::query(
+        "UPDATE regions SET parent_id = (SELECT parent_id FROM regions WHERE id = $1)
+WHERE parent_id = $1",
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await
+    .unwrap();
+    let deleted = sqlx::query("DELETE FROM regions WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .unwrap()
+        .rows_affected()
+        > 0;
+    tx.commit().await.unwrap();
+    notify_orders_changed(&db).await;
+    deleted
+}
+
+/// `None` when a reindeer with that name already exists.
+pub async fn insert_reindeer(
+    db: MyState,
+    name: String,
+    attributes: Map<String, Value>,
+) -> Option<i32> {
+    sqlx::query_scalar(
+        "INSERT INTO reindeer (name, attributes) VALUES ($1, $2)
+ON CONFLICT (name) DO NOTHING
+RETURNING id",
+    )
+    .bind(name)
+    .bind(Json(attributes))
+    .fetch_optional(&db.pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_reindeer(db: MyState, id: i32) -> Option<StoredReindeer> {
+    sqlx::query_as("SELECT id, name, attributes FROM reindeer WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db.pool)
+        .await
+        .unwrap()
+}
+
+pub async fn get_reindeer_by_name(db: MyState, name: &str) -> Option<StoredReindeer> {
+    sqlx::query_as("SELECT id, name, attributes FROM reindeer WHERE name = $1")
+        .bind(name)
+        .fetch_optional(&db.pool)
+        .await
+        .unwrap()
+}
+
+pub async fn get_all_reindeer(db: MyState) -> Vec<StoredReindeer> {
+    sqlx::query_as("SELECT id, name, attributes FROM reindeer ORDER BY id")
         .fetch_all(&db.pool)
         .await
         .unwrap()
 }
+
+/// `false` when the reindeer doesn't exist or another one already has the new name.
+pub async fn update_reindeer(
+    db: MyState,
+    id: i32,
+    name: String,
+    attributes: Map<String, Value>,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query("UPDATE reindeer SET name = $2, attributes = $3 WHERE id = $1")
+        .bind(id)
+        .bind(name)
+        .bind(Json(attributes))
+        .execute(&db.pool)
+        .await?;
+    Ok(updated.rows_affected() > 0)
+}
+
+pub async fn delete_reindeer(db: MyState, id: i32) -> bool {
+    sqlx::query("DELETE FROM reindeer WHERE id = $1")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap()
+        .rows_affected()
+        > 0
+}
+
+/// Stores the reindeer as they entered the contest and their placing in every category.
+pub async fn insert_contest(
+    db: MyState,
+    entries: Vec<(String, Map<String, Value>)>,
+    placings: Vec<(String, String, i32, Option<f64>)>,
+) -> Result<i32, sqlx::Error> {
+    let mut tx = db.pool.begin().await?;
+    let contest_id: i32 = sqlx::query_scalar("INSERT INTO contests DEFAULT VALUES RETURNING id")
+        .fetch_one(&mut *tx)
+        .await?;
+    for (name, attributes) in entries {
+        sqlx::query(
+            "INSERT INTO contest_entries (contest_id, name, attributes) VALUES ($1, $2, $3)",
+        )
+        .bind(contest_id)
+        .bind(name)
+        .bind(Json(attributes))
+        .execute(&mut *tx)
+        .await?;
+    }
+    for (name, category, rank, score) in placings {
+        sqlx::query(
+            "INSERT INTO contest_placings (contest_id, name, category, rank, score)
+VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(contest_id)
+        .bind(name)
+        .bind(category)
+        .bind(rank)
+        .bind(score)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(contest_id)
+}
+
+pub async fn get_contest_entries(db: MyState, name: &str) -> Vec<ContestEntry> {
+    sqlx::query_as(
+        "SELECT c.id AS contest_id, c.held_at, e.attributes
+FROM contest_entries e
+JOIN contests c ON c.id = e.contest_id
+WHERE e.name = $1
+ORDER BY c.held_at, c.id",
+    )
+    .bind(name)
+    .fetch_all(&db.pool)
+    .await
+    .unwrap()
+}
+
+pub async fn get_contest_placings(db: MyState, name: &str) -> Vec<ContestPlacing> {
+    sqlx::query_as(
+        "SELECT contest_id, category, rank, score
+FROM contest_placings
+WHERE name = $1
+ORDER BY contest_id",
+    )
+    .bind(name)
+    .fetch_all(&db.pool)
+    .await
+    .unwrap()
+}
@@ -1,2 +1,5 @@
+pub mod cache;
 pub mod methods;
 pub mod structs;
+#[cfg(test)]
+pub mod testing;
@@ -1,8 +1,23 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+
+use crate::db::cache::QueryCache;
 
 #[derive(Clone)]
 pub struct MyState {
     pub pool: sqlx::PgPool,
+    pub cache: QueryCache,
+}
+
+impl MyState {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        MyState {
+            pool,
+            cache: QueryCache::default(),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -13,8 +28,32 @@ pub struct Order {
     pub quantity: i32,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, sqlx::FromRow)]
 pub struct Region {
     pub id: i32,
     pub name: String,
+    #[serde(default)]
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StoredReindeer {
+    pub id: i32,
+    pub name: String,
+    pub attributes: Json<Map<String, Value>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ContestEntry {
+    pub contest_id: i32,
+    pub held_at: DateTime<Utc>,
+    pub attributes: Json<Map<String, Value>>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ContestPlacing {
+    pub contest_id: i32,
+    pub category: String,
+    pub rank: i32,
+    pub score: Option<f64>,
 }
@@ -0,0 +1,102 @@
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::OnceLock;
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::PgPool;
+use ulid::Ulid;
+
+use crate::db::structs::MyState;
+
+/// Schemas to drop, each with who to tell once it's done.
+type Cleanup = mpsc::Sender<(String, mpsc::Sender<()>)>;
+
+static CLEANUP: OnceLock<Cleanup> = OnceLock::new();
+
+fn database_url() -> String {
+    std::env::var("DATABASE_URL")
+        .expect("DATABASE_URL must point to a PostgreSQL database to run the database tests")
+}
+
+/// There is no async drop, so the schemas are dropped by a thread of its own,
+/// over one connection shared by every test.
+fn cleanup() -> &'static Cleanup {
+    CLEANUP.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<(String, mpsc::Sender<()>)>();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let admin = runtime.block_on(async {
+                PgPoolOptions::new()
+                    .max_connections(1)
+                    .connect_lazy(&database_url())
+                    .unwrap()
+            });
+            for (schema, done) in receiver {
+                runtime.block_on(async {
+                    let _ = sqlx::query(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE"))
+                        .execute(&admin)
+                        .await;
+                });
+                let _ = done.send(());
+            }
+        });
+        sender
+    })
+}
+
+/// A migrated schema of its own for one test, dropped with the value,
+/// so that database tests can run in parallel.
+pub struct TestDb {
+    pub db: MyState,
+    schema: String,
+}
+
+impl TestDb {
+    pub async fn new() -> Self {
+        let url = database_url();
+        let schema = format!("test_{}", Ulid::new().to_string().to_lowercase());
+
+        let admin = PgPool::connect(&url)
+            .await
+            .expect("Failed to connect to the database for testing");
+        sqlx::query(&format!("CREATE SCHEMA {schema}"))
+            .execute(&admin)
+            .await
+            .unwrap();
+        admin.close().await;
+
+        // every connection of the pool only sees the test schema
+        let options = PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", schema.as_str())]);
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        TestDb {
+            db: MyState::new(pool),
+            schema,
+        }
+    }
+}
+
+/// For the routes that never reach the database, it is never connected to.
+pub fn unconnected() -> MyState {
+    MyState::new(PgPoolOptions::new().connect_lazy_with(PgConnectOptions::new()))
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let (done, dropped) = mpsc::channel();
+        // waited for, or the last schemas would outlive the test run
+        if cleanup().send((self.schema.clone(), done)).is_ok() {
+            let _ = dropped.recv();
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use axum::http::{StatusCode, Uri};
 use axum::Router;
+use shuttle_service::SecretStore;
 use sqlx::PgPool;
 
 use crate::days::day00::get_day_0_router;
@@ -7,8 +8,8 @@ use crate::days::day01::get_day_1_router;
 use crate::days::day04::get_day_4_router;
 use crate::days::day05::get_day_5_router;
 use crate::days::day06::get_day_6_router;
-use crate::days::day07::get_day_7_router;
-use crate::days::day08::get_day_8_router;
+use crate::days::day07::{get_day_7_router, CookieConfig};
+use crate::days::day08::{get_day_8_router, PokemonClient};
 use crate::days::day11::get_day_11_router;
 use crate::days::day12::get_day_12_router;
 use crate::days::day13::get_day_13_router;
@@ -20,22 +21,30 @@ use crate::days::day20::get_day_20_router;
 use crate::days::day21::get_day_21_router;
 use crate::days::day22::get_day_22_router;
 use crate::db::structs::MyState;
+use crate::orders::service::get_orders_api_router;
+use crate::orders::stream::get_orders_stream_router;
 
 mod days;
 mod db;
+mod orders;
+mod secrets;
 
 #[shuttle_runtime::main]
-async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::ShuttleAxum {
+async fn main(
+    #[shuttle_shared_db::Postgres] pool: PgPool,
+    #[secrets::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
     let db = init_db(pool).await;
+    let orders_stream = get_orders_stream_router(db.clone());
 
     let router = Router::new()
         .merge(get_day_0_router())
         .nest("/1", get_day_1_router())
-        .nest("/4", get_day_4_router())
+        .nest("/4", get_day_4_router(db.clone()))
         .nest("/5", get_day_5_router())
         .nest("/6", get_day_6_router())
-        .nest("/7", get_day_7_router())
-        .nest("/8", get_day_8_router())
+        .nest("/7", get_day_7_router(CookieConfig::from_secrets(&secrets)))
+        .nest("/8", get_day_8_router(PokemonClient::from_env()))
         .nest("/11", get_day_11_router())
         .nest("/12", get_day_12_router())
         .nest("/13", get_day_13_router(db.clone()))
@@ -46,6 +55,8 @@ async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::Shut
         .nest("/20", get_day_20_router())
         .nest("/21", get_day_21_router())
         .nest("/22", get_day_22_router())
+        .nest("/orders", orders_stream.clone())
+        .nest("/api/v1", get_orders_api_router(db.clone(), orders_stream))
         .fallback(fallback);
 
     Ok(router.into())
@@ -54,7 +65,7 @@ async fn main(#[shuttle_shared_db::Postgres] pool: PgPool) -> shuttle_axum::Shut
 async fn init_db(pool: PgPool) -> MyState {
     sqlx::migrate!().run(&pool).await.unwrap();
 
-    MyState { pool }
+    MyState::new(pool)
 }
 
 async fn fallback(uri: Uri) -> (StatusCode, String) {
@@ -0,0 +1,512 @@
+use std::collections::HashMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::error::ErrorKind;
+
+use crate::db;
+use crate::db::structs::{MyState, Order, Region};
+
+/// Versioned API exposing every order and region capability,
+/// `stream` is the already running `/orders/stream` router.
+pub fn get_orders_api_router(db: MyState, stream: Router) -> Router {
+    Router::new()
+        .route("/reset", post(reset))
+        .nest("/regions", regions_routes())
+        .with_state(db.clone())
+        .nest("/orders", orders_routes().with_state(db).merge(stream))
+}
+
+pub fn orders_routes() -> Router<MyState> {
+    Router::new()
+        .route("/", post(insert_orders))
+        .route("/total", get(get_number_order))
+        .route("/popular", get(get_popular_order))
+}
+
+pub fn regions_routes() -> Router<MyState> {
+    Router::new()
+        .route("/", post(insert_regions))
+        .route("/total", get(get_number_region))
+        .route("/top_list/:number", get(get_top_list))
+        .route("/tree", get(get_region_tree))
+        .route("/:id/parent", put(set_region_parent))
+        .route("/:id", delete(delete_region))
+}
+
+pub async fn reset(State(db): State<MyState>) -> StatusCode {
+    db::methods::reset(db).await;
+
+    StatusCode::OK
+}
+
+/// Rejected data, like an id given twice, is the client's fault.
+fn insert_error(error: sqlx::Error) -> (StatusCode, String) {
+    let status = match error.as_database_error().map(|error| error.kind()) {
+        Some(ErrorKind::Other) | None => StatusCode::INTERNAL_SERVER_ERROR,
+        Some(_) => StatusCode::BAD_REQUEST,
+    };
+    (status, error.to_string())
+}
+
+async fn insert_orders(
+    State(db): State<MyState>,
+    Json(data): Json<Vec<Order>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    db::methods::insert_orders(db, data)
+        .await
+        .map_err(insert_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct OrderTotal {
+    total: i64,
+}
+
+/// Answers 304 when the client already has this exact body.
+fn json_with_etag<T: Serialize>(headers: &HeaderMap, value: &T) -> Response {
+    let body = serde_json::to_vec(value).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(&body);
+    let etag = format!("\"{}\"", hex::encode(&hasher.finalize()[..16]));
+
+    let not_modified = headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*");
+
+    if not_modified {
+        (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response()
+    } else {
+        (
+            StatusCode::OK,
+            [(ETAG, etag), (CONTENT_TYPE, "application/json".to_string())],
+            body,
+        )
+            .into_response()
+    }
+}
+
+async fn get_number_order(headers: HeaderMap, State(db): State<MyState>) -> Response {
+    let total: i64 = db::cache::get_number_order(db).await;
+
+    json_with_etag(&headers, &OrderTotal { total })
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Popular {
+    popular: Value,
+}
+
+async fn get_popular_order(headers: HeaderMap, State(db): State<MyState>) -> Response {
+    let popular: Value = db::cache::get_most_popular_order(db).await;
+
+    json_with_etag(&headers, &Popular { popular })
+}
+
+async fn insert_regions(
+    State(db): State<MyState>,
+    Json(data): Json<Vec<Region>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    db::methods::insert_regions(db, data)
+        .await
+        .map_err(insert_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+/// `?rollup=true` adds the orders of all sub-regions to each region,
+/// `?depth=n` only keeps the regions at level n (roots are 0) and implies the rollup.
+#[derive(Deserialize, Debug)]
+struct Aggregation {
+    #[serde(default)]
+    rollup: bool,
+    depth: Option<i32>,
+}
+
+impl Aggregation {
+    fn rollup(&self) -> bool {
+        self.rollup || self.depth.is_some()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RegionTotal {
+    pub region: String,
+    pub total: i64,
+}
+
+async fn get_number_region(
+    headers: HeaderMap,
+    Query(aggregation): Query<Aggregation>,
+    State(db): State<MyState>,
+) -> Response {
+    let totals: Vec<RegionTotal> =
+        db::cache::get_number_region(db, aggregation.rollup(), aggregation.depth)
+            .await
+            .into_iter()
+            .map(|(region, total)| RegionTotal { region, total })
+            .collect();
+
+    json_with_etag(&headers, &totals)
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct TopGifts {
+    region: String,
+    top_gifts: Vec<String>,
+}
+
+async fn get_top_list(
+    headers: HeaderMap,
+    Path(number): Path<i32>,
+    Query(aggregation): Query<Aggregation>,
+    State(db): State<MyState>,
+) -> Response {
+    let top_list: Vec<TopGifts> =
+        db::cache::get_top_gifts(db, number, aggregation.rollup(), aggregation.depth)
+            .await
+            .into_iter()
+            .map(|(region, top_gifts)| TopGifts {
+                region,
+                top_gifts: get_top_gifts(top_gifts),
+            })
+            .collect();
+
+    json_with_etag(&headers, &top_list)
+}
+
+fn get_top_gifts(s: Option<String>) -> Vec<String> {
+    s.map(|gifts| {
+        gifts
+            .split(',')
+            .map(|gift| gift.trim().to_string())
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct RegionNode {
+    id: i32,
+    name: String,
+    children: Vec<RegionNode>,
+}
+
+async fn get_region_tree(State(db): State<MyState>) -> (StatusCode, Json<Vec<RegionNode>>) {
+    let regions = db::methods::get_regions(db).await;
+
+    let mut children: HashMap<Option<i32>, Vec<&Region>> = HashMap::new();
+    for region in &regions {
+        // a parent that doesn't exist (anymore) makes the region a root
+        let parent_id = region
+            .parent_id
+            .filter(|parent_id| regions.iter().any(|r| r.id == *parent_id));
+        children.entry(parent_id).or_default().push(region);
+    }
+
+    (StatusCode::OK, Json(build_region_nodes(&children, None)))
+}
+
+fn build_region_nodes(
+    children: &HashMap<Option<i32>, Vec<&Region>>,
+    parent_id: Option<i32>,
+) -> Vec<RegionNode> {
+    children
+        .get(&parent_id)
+        .map(|regions| {
+            regions
+                .iter()
+                .map(|region| RegionNode {
+                    id: region.id,
+                    name: region.name.clone(),
+                    children: build_region_nodes(children, Some(region.id)),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Parent {
+    parent_id: Option<i32>,
+}
+
+async fn set_region_parent(
+    Path(id): Path<i32>,
+    State(db): State<MyState>,
+    Json(parent): Json<Parent>,
+) -> StatusCode {
+    if !db::methods::region_exists(db.clone(), id).await {
+        return StatusCode::NOT_FOUND;
+    }
+    if let Some(parent_id) = parent.parent_id {
+        // the new parent must exist and must not be the region itself or one of its descendants
+        if !db::methods::region_exists(db.clone(), parent_id).await
+            || db::methods::get_region_subtree(db.clone(), id)
+                .await
+                .contains(&parent_id)
+        {
+            return StatusCode::BAD_REQUEST;
+        }
+    }
+    db::methods::update_region_parent(db, id, parent.parent_id).await;
+
+    StatusCode::OK
+}
+
+async fn delete_region(Path(id): Path<i32>, State(db): State<MyState>) -> StatusCode {
+    if db::methods::delete_region(db, id).await {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    use crate::db::testing::TestDb;
+
+    use super::*;
+
+    async fn setup_test_server() -> (TestServer, TestDb) {
+        let test_db = TestDb::new().await;
+        let app = get_orders_api_router(test_db.db.clone(), Router::new());
+
+        (TestServer::new(app).unwrap(), test_db)
+    }
+
+    #[tokio::test]
+    async fn orders() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+
+        // Send the request.
+        let response = server.post("/reset").await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server
+            .post("/orders")
+            .json(
+                &json!([{"id":1,"region_id":2,"gift_name":"Toy Train","quantity":5},
+    {"id":2,"region_id":2,"gift_name":"Doll","quantity":8}]),
+            )
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server.get("/orders/total").await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({"total":13}));
+
+        // Send the request.
+        let response = server.get("/orders/popular").await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({"popular":"Doll"}));
+
+        // The second order 3 is refused, and the first with it.
+        // Send the request.
+        let response = server
+            .post("/orders")
+            .json(
+                &json!([{"id":3,"region_id":2,"gift_name":"Doll","quantity":1},
+    {"id":3,"region_id":2,"gift_name":"Doll","quantity":1}]),
+            )
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server.get("/orders/total").await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!({"total":13}));
+    }
+
+    #[tokio::test]
+    async fn etag() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+        setup_region_tree(&server).await;
+
+        // Send the request.
+        let response = server.get("/regions/total").await;
+
+        response.assert_status(StatusCode::OK);
+
+        let etag = response.header(ETAG);
+
+        // Nothing changed.
+        // Send the request.
+        let response = server
+            .get("/regions/total")
+            .add_header(IF_NONE_MATCH, etag.clone())
+            .await;
+
+        response.assert_status(StatusCode::NOT_MODIFIED);
+
+        // Send the request.
+        let response = server
+            .post("/orders")
+            .json(&json!([{"id":5,"region_id":5,"gift_name":"Drone","quantity":1}]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        // The insert invalidated the cached totals.
+        // Send the request.
+        let response = server
+            .get("/regions/total")
+            .add_header(IF_NONE_MATCH, etag)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+  {"region":"Asia","total":3},
+  {"region":"Belgium","total":4},
+  {"region":"France","total":8},
+  {"region":"Paris","total":5}]));
+    }
+
+    async fn setup_region_tree(server: &TestServer) {
+        // Send the request.
+        let response = server.post("/reset").await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server
+            .post("/regions")
+            .json(&json!([
+    {"id":1,"name":"Europe"},
+    {"id":2,"name":"France","parent_id":1},
+    {"id":3,"name":"Paris","parent_id":2},
+    {"id":4,"name":"Belgium","parent_id":1},
+    {"id":5,"name":"Asia"}]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server
+            .post("/orders")
+            .json(&json!([
+    {"id":1,"region_id":3,"gift_name":"Toy Train","quantity":5},
+    {"id":2,"region_id":2,"gift_name":"Doll","quantity":8},
+    {"id":3,"region_id":4,"gift_name":"Toy Train","quantity":4},
+    {"id":4,"region_id":5,"gift_name":"Drone","quantity":2}]))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rollup() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+        setup_region_tree(&server).await;
+
+        // Send the request.
+        let response = server
+            .get("/regions/total")
+            .add_query_param("rollup", true)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+  {"region":"Asia","total":2},
+  {"region":"Belgium","total":4},
+  {"region":"Europe","total":17},
+  {"region":"France","total":13},
+  {"region":"Paris","total":5}]));
+
+        // Send the request.
+        let response = server
+            .get("/regions/total")
+            .add_query_param("depth", 1)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+  {"region":"Belgium","total":4},
+  {"region":"France","total":13}]));
+
+        // Send the request.
+        let response = server
+            .get("/regions/top_list/1")
+            .add_query_param("depth", 0)
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+  {"region":"Asia","top_gifts":["Drone"]},
+  {"region":"Europe","top_gifts":["Toy Train"]}]));
+    }
+
+    #[tokio::test]
+    async fn tree() {
+        // Run the application for testing.
+        let (server, _test_db) = setup_test_server().await;
+        setup_region_tree(&server).await;
+
+        // Europe can't become a child of Paris.
+        // Send the request.
+        let response = server
+            .put("/regions/1/parent")
+            .json(&json!({"parent_id":3}))
+            .await;
+
+        response.assert_status(StatusCode::BAD_REQUEST);
+
+        // Send the request.
+        let response = server
+            .put("/regions/5/parent")
+            .json(&json!({"parent_id":1}))
+            .await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server.delete("/regions/2").await;
+
+        response.assert_status(StatusCode::OK);
+
+        // Send the request.
+        let response = server.get("/regions/tree").await;
+
+        response.assert_status(StatusCode::OK);
+
+        response.assert_json(&json!([
+  {"id":1,"name":"Europe","children":[
+    {"id":5,"name":"Asia","children":[]},
+    {"id":4,"name":"Belgium","children":[]},
+    {"id":3,"name":"Paris","children":[]}]}]));
+    }
+}
@@ -0,0 +1,219 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::routing::get;
+use axum::Router;
+use futures::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::PgListener;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::db;
+use crate::db::methods::ORDERS_CHANNEL;
+use crate::db::structs::MyState;
+use crate::orders::service::RegionTotal;
+
+#[derive(Clone)]
+struct StreamState {
+    db: MyState,
+    snapshots: broadcast::Sender<String>,
+}
+
+pub fn get_orders_stream_router(db: MyState) -> Router {
+    let stream_state = StreamState {
+        snapshots: spawn_listener(db.clone()),
+        db,
+    };
+    Router::new()
+        .route("/stream", get(stream_orders))
+        .with_state(stream_state)
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct OrdersSnapshot {
+    total: i64,
+    popular: Value,
+    regions: Vec<RegionTotal>,
+}
+
+async fn get_snapshot(db: MyState) -> String {
+    let snapshot = OrdersSnapshot {
+        total: db::cache::get_number_order(db.clone()).await,
+        popular: db::cache::get_most_popular_order(db.clone()).await,
+        regions: db::cache::get_number_region(db, false, None)
+            .await
+            .into_iter()
+            .map(|(region, total)| RegionTotal { region, total })
+            .collect(),
+    };
+    serde_json::to_string(&snapshot).unwrap()
+}
+
+/// Doubled after each failed attempt to listen, up to [`MAX_BACKOFF`].
+const BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+async fn listen(db: &MyState) -> Result<(PgListener, String), sqlx::Error> {
+    let mut listener = PgListener::connect_with(&db.pool).await?;
+    listener.listen(ORDERS_CHANNEL).await?;
+    let schema = sqlx::query_scalar("SELECT current_schema()")
+        .fetch_one(&db.pool)
+        .await?;
+    Ok((listener, schema))
+}
+
+/// Sends the subscribers a snapshot of the orders as they are now.
+async fn publish(db: &MyState, sender: &broadcast::Sender<String>) {
+    // the change may come from another instance sharing the database
+    db.cache.invalidate();
+    if sender.receiver_count() == 0 {
+        return;
+    }
+    // a reset running at the same time can make the queries panic,
+    // only that snapshot is lost
+    if let Ok(snapshot) = tokio::spawn(get_snapshot(db.clone())).await {
+        let _ = sender.send(snapshot);
+    }
+}
+
+/// Holds the only LISTEN connection and computes one snapshot per notification,
+/// shared by every subscriber of the stream.
+fn spawn_listener(db: MyState) -> broadcast::Sender<String> {
+    let (snapshots, _) = broadcast::channel(16);
+    let sender = snapshots.clone();
+
+    tokio::spawn(async move {
+        let mut backoff = BACKOFF;
+        let mut reconnecting = false;
+        loop {
+            let (mut listener, schema) = match listen(&db).await {
+                Ok(listening) => listening,
+                Err(error) => {
+                    tracing::error!(
+                        "Can't listen to order changes, retrying in {backoff:?}: {error}"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+            backoff = BACKOFF;
+
+            // notifications may have been missed while not listening
+            if reconnecting {
+                publish(&db, &sender).await;
+            }
+            reconnecting = true;
+            let error = loop {
+                match listener.recv().await {
+                    Ok(notification) if notification.payload() == schema => {
+                        publish(&db, &sender).await
+                    }
+                    Ok(_) => {}
+                    Err(error) => break error,
+                }
+            };
+            tracing::warn!("Stopped listening to order changes, reconnecting: {error}");
+        }
+    });
+
+    snapshots
+}
+
+async fn stream_orders(
+    State(state): State<StreamState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // subscribe first so that no change is missed while the first snapshot is computed
+    let receiver = state.snapshots.subscribe();
+    let current = get_snapshot(state.db).await;
+
+    let updates = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(snapshot) => return Some((snapshot, receiver)),
+                // a slow client only needs the latest state
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    let events = stream::once(async { current })
+        .chain(updates)
+        .map(|snapshot| Ok(Event::default().event("orders").data(snapshot)));
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_test::{TestServer, TestServerConfig};
+
+    use crate::db::structs::Order;
+    use crate::db::testing::TestDb;
+
+    use super::*;
+
+    async fn next_snapshot(response: &mut reqwest::Response) -> OrdersSnapshot {
+        loop {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+                .await
+                .expect("No event received")
+                .unwrap()
+                .unwrap();
+            let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+            if let Some(data) = chunk.lines().find_map(|line| line.strip_prefix("data: ")) {
+                return serde_json::from_str(data).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let test_db = TestDb::new().await;
+        let db = test_db.db.clone();
+
+        // Run the application for testing.
+        let config = TestServerConfig::builder().http_transport().build();
+        let server =
+            TestServer::new_with_config(get_orders_stream_router(db.clone()), config).unwrap();
+        let url = server.server_address().unwrap().join("stream").unwrap();
+
+        // Send the request.
+        let mut response = reqwest::get(url).await.unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let snapshot = next_snapshot(&mut response).await;
+        assert_eq!(snapshot.total, 0);
+        assert_eq!(snapshot.popular, Value::Null);
+
+        db::methods::insert_orders(
+            db,
+            vec![
+                Order {
+                    id: 1,
+                    region_id: 1,
+                    gift_name: "Toy Train".to_string(),
+                    quantity: 5,
+                },
+                Order {
+                    id: 2,
+                    region_id: 1,
+                    gift_name: "Doll".to_string(),
+                    quantity: 3,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let snapshot = next_snapshot(&mut response).await;
+        assert_eq!(snapshot.total, 8);
+        assert_eq!(snapshot.popular, "Toy Train");
+    }
+}
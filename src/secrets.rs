@@ -0,0 +1,32 @@
+use serde::Serialize;
+use shuttle_runtime::{async_trait, Error, Factory, ResourceBuilder};
+use shuttle_service::{SecretStore, Type};
+
+/// The `Secrets.toml` of the project, `Secrets.dev.toml` when running locally.
+#[derive(Serialize)]
+pub struct Secrets;
+
+#[async_trait]
+impl ResourceBuilder<SecretStore> for Secrets {
+    const TYPE: Type = Type::Secrets;
+
+    type Config = ();
+
+    type Output = SecretStore;
+
+    fn new() -> Self {
+        Secrets
+    }
+
+    fn config(&self) -> &Self::Config {
+        &()
+    }
+
+    async fn output(self, factory: &mut dyn Factory) -> Result<Self::Output, Error> {
+        Ok(SecretStore::new(factory.get_secrets().await?))
+    }
+
+    async fn build(build_data: &Self::Output) -> Result<SecretStore, Error> {
+        Ok(build_data.clone())
+    }
+}